@@ -0,0 +1,116 @@
+use crate::gotify::GotifyClient;
+use crate::stream::{ConnectionState, StreamEvents, StreamInfo, StreamSettings, StreamSupervisor};
+use log::info;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Live connections to Gotify servers, keyed by `ServerConfig.id`.
+///
+/// Every connection has its own REST client and WebSocket stream, so several
+/// servers can be listened to at the same time.
+#[derive(Default)]
+pub struct ConnectionManager {
+    clients: Mutex<HashMap<String, GotifyClient>>,
+    streams: StreamSupervisor,
+}
+
+impl ConnectionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connects `server_id`, replacing an existing connection to it while
+    /// leaving the other servers untouched.
+    pub fn connect<E: StreamEvents>(
+        &self,
+        server_id: &str,
+        client: GotifyClient,
+        settings: StreamSettings,
+        events: E,
+    ) {
+        self.streams
+            .start(server_id, client.clone(), settings, events);
+        self.clients
+            .lock()
+            .unwrap()
+            .insert(server_id.to_string(), client);
+        info!("Connected server {}", server_id);
+    }
+
+    /// Drops the connection to `server_id`, returning whether there was one.
+    pub fn disconnect(&self, server_id: &str) -> bool {
+        self.streams.stop(server_id);
+        let removed = self.clients.lock().unwrap().remove(server_id).is_some();
+        if removed {
+            info!("Disconnected server {}", server_id);
+        }
+        removed
+    }
+
+    /// Drops every connection, returning the ids of the servers that were connected.
+    pub fn disconnect_all(&self) -> Vec<String> {
+        self.streams.stop_all();
+        let mut server_ids: Vec<String> = self
+            .clients
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(id, _)| id)
+            .collect();
+        server_ids.sort();
+        server_ids
+    }
+
+    /// Whether `server_id` has a live connection. A server whose stream gave
+    /// up because the token was rejected counts as disconnected, so it is
+    /// started again by the next connect.
+    pub fn is_connected(&self, server_id: &str) -> bool {
+        self.clients.lock().unwrap().contains_key(server_id)
+            && !matches!(self.status(server_id), ConnectionState::AuthFailed { .. })
+    }
+
+    pub fn server_ids(&self) -> Vec<String> {
+        let mut server_ids: Vec<String> = self.clients.lock().unwrap().keys().cloned().collect();
+        server_ids.sort();
+        server_ids
+    }
+
+    /// Resolves the client a command should use. Without an explicit
+    /// `server_id` the only connected server is used; with several servers
+    /// connected the caller has to pick one.
+    pub fn client(&self, server_id: Option<&str>) -> Result<(String, GotifyClient), String> {
+        let clients = self.clients.lock().unwrap();
+        match server_id {
+            Some(id) => clients
+                .get(id)
+                .map(|client| (id.to_string(), client.clone()))
+                .ok_or_else(|| format!("Server {} is not connected.", id)),
+            None => match clients.len() {
+                0 => Err("Not connected to Gotify server. Please connect first.".to_string()),
+                1 => Ok(clients
+                    .iter()
+                    .map(|(id, client)| (id.clone(), client.clone()))
+                    .next()
+                    .unwrap()),
+                _ => Err("Several servers are connected; please specify server_id.".to_string()),
+            },
+        }
+    }
+
+    pub fn status(&self, server_id: &str) -> ConnectionState {
+        self.streams.status(server_id)
+    }
+
+    pub fn statuses(&self) -> HashMap<String, ConnectionState> {
+        self.streams.statuses()
+    }
+
+    pub fn stream_infos(&self) -> Vec<StreamInfo> {
+        self.streams.infos()
+    }
+
+    /// Skips the backoff wait of every reconnecting stream.
+    pub fn reconnect_now(&self) -> usize {
+        self.streams.reconnect_now()
+    }
+}
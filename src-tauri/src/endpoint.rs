@@ -0,0 +1,117 @@
+use crate::gotify::GotifyError;
+use percent_encoding::percent_decode_str;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+// 通过 Unix 域套接字访问时请求中使用的主机名
+const UNIX_SOCKET_HOST: &str = "localhost";
+
+/// Where a Gotify server is reached. REST endpoints and the stream are
+/// resolved against `base`, so servers mounted under a subpath keep it.
+///
+/// Accepted forms:
+/// - `https://push.example.com`, `http://host:8080/gotify/`, `http://[::1]:8080`
+/// - `unix:///run/gotify.sock` for a server listening on a Unix domain socket
+/// - `http+unix://%2Frun%2Fgotify.sock/gotify/` for a socket with a subpath
+///   (the socket path is percent-encoded in the host part)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoint {
+    // 路径总以 / 结尾，相对路径会拼接在子路径之后
+    base: Url,
+    unix_socket: Option<PathBuf>,
+}
+
+fn decode(value: &str) -> String {
+    percent_decode_str(value).decode_utf8_lossy().into_owned()
+}
+
+impl Endpoint {
+    pub fn parse(server_url: &str) -> Result<Self, GotifyError> {
+        let url =
+            Url::parse(server_url.trim()).map_err(|e| GotifyError::InvalidUrl(e.to_string()))?;
+        let invalid = |reason: &str| GotifyError::InvalidUrl(format!("{}: {}", reason, url));
+
+        let (mut base, unix_socket) = match url.scheme() {
+            "http" | "https" => {
+                if url.host_str().filter(|host| !host.is_empty()).is_none() {
+                    return Err(invalid("missing host"));
+                }
+                (url.clone(), None)
+            }
+            "unix" => {
+                let path = decode(url.path());
+                if path.is_empty() || path.ends_with('/') {
+                    return Err(invalid("missing socket path"));
+                }
+                (Self::unix_base("/"), Some(PathBuf::from(path)))
+            }
+            "http+unix" => {
+                let path = url
+                    .host_str()
+                    .map(decode)
+                    .filter(|path| !path.is_empty())
+                    .ok_or_else(|| invalid("missing socket path"))?;
+                (Self::unix_base(url.path()), Some(PathBuf::from(path)))
+            }
+            scheme => return Err(invalid(&format!("unsupported scheme {}", scheme))),
+        };
+
+        base.set_query(None);
+        base.set_fragment(None);
+        if !base.path().ends_with('/') {
+            let path = format!("{}/", base.path());
+            base.set_path(&path);
+        }
+        Ok(Self { base, unix_socket })
+    }
+
+    fn unix_base(path: &str) -> Url {
+        let mut base = Url::parse(&format!("http://{}/", UNIX_SOCKET_HOST)).unwrap();
+        base.set_path(path);
+        base
+    }
+
+    /// Socket path when the server is reached through a Unix domain socket.
+    pub fn unix_socket(&self) -> Option<&Path> {
+        self.unix_socket.as_deref()
+    }
+
+    /// URL of a REST endpoint such as `message?limit=10`.
+    pub fn url(&self, endpoint: &str) -> String {
+        self.join(endpoint).into()
+    }
+
+    /// WebSocket URL of the message stream (`ws://` or `wss://`).
+    pub fn stream_url(&self) -> String {
+        let mut url = self.join("stream");
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        // http(s) 与 ws(s) 同为特殊协议，切换不会失败
+        let _ = url.set_scheme(scheme);
+        url.into()
+    }
+
+    fn join(&self, endpoint: &str) -> Url {
+        // 以 / 开头会丢掉子路径，统一按相对路径处理
+        let endpoint = endpoint.trim_start_matches('/');
+        self.base
+            .join(endpoint)
+            .unwrap_or_else(|_| self.base.clone())
+    }
+
+    /// Routes all connections of the REST client through the Unix socket,
+    /// if one is configured. Proxy settings do not apply to sockets.
+    pub fn apply(
+        &self,
+        builder: reqwest::ClientBuilder,
+    ) -> Result<reqwest::ClientBuilder, GotifyError> {
+        match &self.unix_socket {
+            None => Ok(builder),
+            #[cfg(unix)]
+            Some(path) => Ok(builder.unix_socket(path.clone())),
+            #[cfg(not(unix))]
+            Some(_) => Err(GotifyError::InvalidUrl(
+                "Unix domain sockets are not supported on this platform".to_string(),
+            )),
+        }
+    }
+}
@@ -1,10 +1,20 @@
+use base64::Engine;
+use futures_util::{stream, Stream};
 use log::info;
-use reqwest::{Client, Error as ReqwestError};
-use serde::Serialize;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{Client as HttpClient, Error as ReqwestError};
+use serde::{Deserialize, Serialize};
 use serde_json::Error as JsonError;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
 use thiserror::Error;
-use tokio::sync::mpsc;
-use url::Url;
+
+use crate::endpoint::Endpoint;
+use crate::proxy::ProxyOptions;
+use crate::tls::{TlsConfig, TlsOptions};
+
+/// Header Gotify reads the client or application token from.
+pub const AUTH_HEADER: &str = "X-Gotify-Key";
 
 #[derive(Debug, Error)]
 pub enum GotifyError {
@@ -22,10 +32,33 @@ pub enum GotifyError {
     InvalidUrl(String),
     #[error("Request failed: {0}")]
     RequestError(String),
+    #[error("WebSocket error: {0}")]
+    StreamError(String),
+    #[error("Proxy error: {0}")]
+    ProxyError(String),
+    #[error("Invalid custom header: {0}")]
+    InvalidHeader(String),
+    #[error("Invalid TLS configuration: {0}")]
+    TlsConfig(String),
+    #[error("Server certificate could not be verified: {0}")]
+    CertificateVerification(String),
+    #[error("Server certificate does not match the pinned fingerprint (expected {expected}, got {actual})")]
+    CertificatePinMismatch { expected: String, actual: String },
+    #[error("Server version {actual} is too old for {feature} (requires {required} or newer)")]
+    Unsupported {
+        feature: &'static str,
+        required: String,
+        actual: String,
+    },
+}
+
+// 证书校验失败单独报告，便于和普通的网络错误区分
+fn network_error(err: ReqwestError) -> GotifyError {
+    crate::tls::certificate_error(&err).unwrap_or(GotifyError::NetworkError(err))
 }
 
 impl GotifyError {
-    fn from_status_code(status: reqwest::StatusCode, body: String) -> Self {
+    pub fn from_status_code(status: reqwest::StatusCode, body: String) -> Self {
         match status {
             reqwest::StatusCode::UNAUTHORIZED => Self::AuthFailed(body),
             reqwest::StatusCode::FORBIDDEN => {
@@ -40,6 +73,50 @@ impl GotifyError {
     }
 }
 
+/// Server build information returned by `/version`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct VersionInfo {
+    pub version: String,
+    pub commit: String,
+    #[serde(default, alias = "buildDate")]
+    pub build_date: String,
+}
+
+/// Parses `major.minor.patch`, ignoring a leading `v` and any pre-release or
+/// build suffix. Development builds (e.g. `unknown`) yield `None`.
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+/// Optional API features that depend on the server version.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    Plugins,
+    ApplicationDefaultPriority,
+}
+
+impl Feature {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Plugins => "plugins",
+            Self::ApplicationDefaultPriority => "application default priority",
+        }
+    }
+
+    pub fn min_version(&self) -> (u64, u64, u64) {
+        match self {
+            Self::Plugins => (2, 0, 0),
+            Self::ApplicationDefaultPriority => (2, 4, 0),
+        }
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct Message {
     pub id: u64,
@@ -51,50 +128,394 @@ pub struct Message {
     #[serde(alias = "appid")]
     pub app_id: u64,
     pub extras: Option<serde_json::Value>,
+    // 消息来源服务器（ServerConfig.id），由客户端标记，服务器返回的数据中没有该字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_id: Option<String>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+/// Paging block of Gotify's message list responses.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Paging {
+    pub size: u64,
+    // 本页最后一条（最旧）消息的 id，作为下一页请求的 since
+    pub since: u64,
+    pub limit: u64,
+    // 下一页的地址，最后一页没有该字段
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+/// One page of messages, newest first.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct PagedMessages {
+    pub messages: Vec<Message>,
+    pub paging: Paging,
+}
+
+impl PagedMessages {
+    /// The `since` value that requests the next (older) page, or `None` on
+    /// the last page.
+    pub fn next_since(&self) -> Option<u64> {
+        self.paging.next.as_ref().map(|_| self.paging.since)
+    }
+}
+
+/// A message to publish. Gotify only accepts new messages through an
+/// application token, which decides the sender shown to clients.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct NewMessage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub message: String,
+    // 未设置时服务器使用应用的默认优先级
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extras: Option<serde_json::Value>,
+}
+
+impl NewMessage {
+    pub fn new(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Replaces all extras, e.g. with namespaces the helpers below don't cover.
+    pub fn extras(mut self, extras: serde_json::Value) -> Self {
+        self.extras = Some(extras);
+        self
+    }
+
+    /// Renders the message as markdown (`client::display`).
+    pub fn markdown(self) -> Self {
+        self.extra("client::display", "contentType", "text/markdown".into())
+    }
+
+    /// Opens `url` when the notification is clicked (`client::notification`).
+    pub fn click_url(self, url: &str) -> Self {
+        self.extra(
+            "client::notification",
+            "click",
+            serde_json::json!({ "url": url }),
+        )
+    }
+
+    /// Shows `url` as a large image in the notification (`client::notification`).
+    pub fn big_image_url(self, url: &str) -> Self {
+        self.extra("client::notification", "bigImageUrl", url.into())
+    }
+
+    /// Sets `key` inside the extras `namespace`, keeping the other keys.
+    pub fn extra(mut self, namespace: &str, key: &str, value: serde_json::Value) -> Self {
+        let extras = self.extras.get_or_insert_with(|| serde_json::json!({}));
+        if !extras.is_object() {
+            *extras = serde_json::json!({});
+        }
+        let entry = extras
+            .as_object_mut()
+            .unwrap()
+            .entry(namespace)
+            .or_insert_with(|| serde_json::json!({}));
+        if !entry.is_object() {
+            *entry = serde_json::json!({});
+        }
+        entry
+            .as_object_mut()
+            .unwrap()
+            .insert(key.to_string(), value);
+        self
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 pub struct Application {
     pub id: u64,
     pub name: String,
     pub description: String,
     pub token: Option<String>,
+    // 内置应用（如插件创建的应用）不能删除
+    #[serde(default)]
+    pub internal: bool,
+    // 应用图标在服务器上的相对路径
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default, alias = "defaultPriority")]
+    pub default_priority: i32,
+}
+
+/// A client token registered on the server, e.g. one per device.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct Client {
+    pub id: u64,
+    pub name: String,
+    pub token: String,
+    #[serde(default, alias = "lastUsed")]
+    pub last_used: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ClientParams<'a> {
+    name: &'a str,
+}
+
+/// Outcome of [`GotifyClient::rotate_token`].
+#[derive(Debug, Clone)]
+pub struct TokenRotation {
+    /// The newly created client token.
+    pub created: Client,
+    /// Same connection settings, authenticated with the new token.
+    pub client: GotifyClient,
+    /// Entry of the replaced token, still valid until it is revoked.
+    pub previous: Option<Client>,
+}
+
+/// A server plugin and its current state.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct Plugin {
+    pub id: u64,
+    pub name: String,
+    pub token: String,
+    #[serde(alias = "modulePath")]
+    pub module_path: String,
+    pub enabled: bool,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub website: Option<String>,
+    #[serde(default)]
+    pub license: Option<String>,
+    // 插件支持的功能，例如 "configurer"、"displayer"、"messenger"、"webhooker"、"storager"
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+/// A Gotify user account.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct User {
+    pub id: u64,
+    pub name: String,
+    pub admin: bool,
+}
+
+// 创建和更新用户时提交的字段；更新时密码为空表示不修改
+#[derive(Serialize)]
+struct UserParams<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pass: Option<&'a str>,
+    admin: bool,
+}
+
+// 创建和更新应用时提交的字段
+#[derive(Serialize)]
+struct ApplicationParams<'a> {
+    name: &'a str,
+    description: &'a str,
+    #[serde(rename = "defaultPriority")]
+    default_priority: i32,
+}
+
+/// Per-server connection settings shared by REST requests and the stream.
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    pub tls: TlsOptions,
+    pub proxy: ProxyOptions,
+    // 反向代理要求的额外请求头，例如 Cloudflare Access 的服务令牌
+    pub headers: BTreeMap<String, String>,
+    pub basic_auth: Option<BasicAuth>,
+}
+
+/// HTTP basic auth credentials for a reverse proxy in front of the server.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct BasicAuth {
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+}
+
+/// Connection settings sent with a login or a config update. Fields left
+/// out keep the stored value; `null` or an empty value (`{}` headers, a
+/// blank basic auth username) clears it. A cleared proxy means the global one.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ConnectionUpdate {
+    #[serde(deserialize_with = "crate::store::double_option")]
+    pub proxy: Option<Option<ProxyOptions>>,
+    #[serde(deserialize_with = "crate::store::double_option")]
+    pub headers: Option<Option<BTreeMap<String, String>>>,
+    #[serde(deserialize_with = "crate::store::double_option")]
+    pub basic_auth: Option<Option<BasicAuth>>,
+}
+
+impl ConnectionUpdate {
+    /// Applies the update to the stored settings of one server.
+    pub fn apply(
+        self,
+        proxy: &mut Option<ProxyOptions>,
+        headers: &mut BTreeMap<String, String>,
+        basic_auth: &mut Option<BasicAuth>,
+    ) {
+        if let Some(update) = self.proxy {
+            *proxy = update;
+        }
+        if let Some(update) = self.headers {
+            *headers = update.unwrap_or_default();
+        }
+        if let Some(update) = self.basic_auth {
+            *basic_auth = update.filter(|auth| !auth.username.trim().is_empty());
+        }
+    }
+}
+
+impl ClientOptions {
+    /// Builds the headers sent with every REST request and the stream
+    /// handshake. The Gotify token header cannot be overridden.
+    pub fn header_map(&self) -> Result<HeaderMap, GotifyError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = name.trim();
+            let header = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| GotifyError::InvalidHeader(format!("invalid name {:?}", name)))?;
+            if header.as_str().eq_ignore_ascii_case(AUTH_HEADER) {
+                return Err(GotifyError::InvalidHeader(format!(
+                    "{} is set from the server token",
+                    AUTH_HEADER
+                )));
+            }
+            let mut value = HeaderValue::from_str(value.trim())
+                .map_err(|_| GotifyError::InvalidHeader(format!("invalid value for {}", name)))?;
+            value.set_sensitive(true);
+            crate::logging::register_secret(value.to_str().unwrap_or_default());
+            headers.insert(header, value);
+        }
+
+        if let Some(auth) = &self.basic_auth {
+            if auth.username.contains(':') {
+                return Err(GotifyError::InvalidHeader(
+                    "basic auth username must not contain ':'".to_string(),
+                ));
+            }
+            crate::logging::register_secret(&auth.password);
+            let credentials = base64::engine::general_purpose::STANDARD
+                .encode(format!("{}:{}", auth.username, auth.password));
+            let mut value = HeaderValue::from_str(&format!("Basic {}", credentials))
+                .map_err(|_| GotifyError::InvalidHeader("invalid basic auth".to_string()))?;
+            value.set_sensitive(true);
+            crate::logging::register_secret(&credentials);
+            headers.insert(AUTHORIZATION, value);
+        }
+        Ok(headers)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct GotifyClient {
     base_url: String,
+    // 解析后的服务器地址（子路径、Unix 域套接字）
+    endpoint: Endpoint,
     token: String,
-    client: Client,
-    // 用于发送新消息的通道发送端
-    message_tx: Option<mpsc::UnboundedSender<Message>>,
+    client: HttpClient,
+    // REST 和 WebSocket 共用的 TLS 设置
+    tls: TlsConfig,
+    proxy: ProxyOptions,
+    // 附加到每个请求（包括 WebSocket 握手）的请求头
+    headers: HeaderMap,
+    // 检测到的服务器版本，克隆出的客户端共享同一份
+    version: Arc<RwLock<Option<VersionInfo>>>,
+}
+
+// 按扩展名确定上传图片的类型，与服务器允许的格式一致
+fn image_mime_type(file_name: &str) -> &'static str {
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        _ => "application/octet-stream",
+    }
 }
 
 impl GotifyClient {
-    pub fn new(server_url: &str, token: &str) -> Result<Self, GotifyError> {
+    /// Creates a client using the server's TLS trust and proxy settings.
+    pub fn with_options(
+        server_url: &str,
+        token: &str,
+        options: &ClientOptions,
+    ) -> Result<Self, GotifyError> {
         let base_url = server_url.trim_end_matches('/').to_string();
+        let endpoint = Endpoint::parse(server_url)?;
 
-        // Validate URL
-        match Url::parse(&base_url) {
-            Ok(_) => {}
-            Err(e) => return Err(GotifyError::InvalidUrl(e.to_string())),
+        let tls = options.tls.build()?;
+        let headers = options.header_map()?;
+        let mut builder = HttpClient::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .default_headers(headers.clone());
+        builder = options.proxy.apply(builder)?;
+        builder = endpoint.apply(builder)?;
+        // 固定证书时由 rustls 在握手中核对指纹，令牌不会发给指纹不符的服务器
+        if let Some(config) = &tls.pinned {
+            builder = builder.use_preconfigured_tls(config.clone());
+        } else if let Some(connector) = &tls.connector {
+            builder = builder.use_preconfigured_tls(connector.clone());
         }
+        let client = builder.build().map_err(GotifyError::NetworkError)?;
 
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .map_err(GotifyError::NetworkError)?;
+        crate::logging::register_secret(token);
 
         Ok(Self {
             base_url,
+            endpoint,
             token: token.to_string(),
             client,
-            message_tx: None,
+            tls,
+            proxy: options.proxy.clone(),
+            headers,
+            version: Arc::new(RwLock::new(None)),
         })
     }
 
-    pub fn set_message_sender(&mut self, tx: mpsc::UnboundedSender<Message>) {
-        self.message_tx = Some(tx);
+    /// Creates a client token named `client_name` by logging in with the
+    /// user's credentials (basic auth on `POST /client`). The password is only
+    /// used for this request.
+    pub async fn login(
+        server_url: &str,
+        username: &str,
+        password: &str,
+        client_name: &str,
+        options: &ClientOptions,
+    ) -> Result<Client, GotifyError> {
+        let anonymous = Self::with_options(server_url, "", options)?;
+        let req_body = serde_json::to_string(&ClientParams { name: client_name })
+            .map_err(GotifyError::JsonError)?;
+
+        let url = anonymous.url("client");
+        info!("POST {} (basic auth as {})", url, username);
+        let request = anonymous
+            .client
+            .post(&url)
+            .basic_auth(username, Some(password))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(req_body);
+        let resp = anonymous.send(request).await?;
+        let body = Self::handle_response(resp).await?;
+        let client: Client = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        crate::logging::register_secret(&client.token);
+        Ok(client)
     }
 
     pub fn get_base_url(&self) -> &str {
@@ -105,51 +526,121 @@ impl GotifyClient {
         &self.token
     }
 
-    async fn get(&self, endpoint: &str) -> Result<reqwest::Response, GotifyError> {
-        let url = if endpoint.contains('?') {
-            format!("{}/{}&token={}", self.base_url, endpoint, self.token)
-        } else {
-            format!("{}/{}?token={}", self.base_url, endpoint, self.token)
+    /// The server version detected by [`Self::get_version`], if any.
+    pub fn server_version(&self) -> Option<VersionInfo> {
+        self.version.read().unwrap().clone()
+    }
+
+    pub fn set_server_version(&self, version: VersionInfo) {
+        *self.version.write().unwrap() = Some(version);
+    }
+
+    /// Fails with [`GotifyError::Unsupported`] when the detected server version
+    /// is older than `feature` needs. Unknown or unparsable versions pass.
+    pub fn require(&self, feature: Feature) -> Result<(), GotifyError> {
+        let Some(info) = self.server_version() else {
+            return Ok(());
         };
-        info!("GET {}", url);
-        let resp = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .map_err(GotifyError::NetworkError)?;
+        match parse_version(&info.version) {
+            Some(actual) if actual < feature.min_version() => {
+                let (major, minor, patch) = feature.min_version();
+                Err(GotifyError::Unsupported {
+                    feature: feature.name(),
+                    required: format!("{}.{}.{}", major, minor, patch),
+                    actual: info.version,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Same server and TLS settings, authenticated with another client token.
+    pub fn with_token(&self, token: &str) -> Self {
+        crate::logging::register_secret(token);
+        Self {
+            token: token.to_string(),
+            ..self.clone()
+        }
+    }
+
+    pub fn tls(&self) -> &TlsConfig {
+        &self.tls
+    }
+
+    pub fn proxy(&self) -> &ProxyOptions {
+        &self.proxy
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// WebSocket endpoint of the message stream. The token is not part of the
+    /// URL; it is sent in the `X-Gotify-Key` header during the handshake.
+    pub fn stream_url(&self) -> String {
+        self.endpoint.stream_url()
+    }
+
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    fn url(&self, endpoint: &str) -> String {
+        self.endpoint.url(endpoint)
+    }
+
+    // 令牌通过请求头传递，不出现在 URL 和日志中
+    fn request(&self, method: reqwest::Method, endpoint: &str) -> reqwest::RequestBuilder {
+        self.request_with_token(method, endpoint, &self.token)
+    }
+
+    fn request_with_token(
+        &self,
+        method: reqwest::Method,
+        endpoint: &str,
+        token: &str,
+    ) -> reqwest::RequestBuilder {
+        let url = self.url(endpoint);
+        info!("{} {}", method, url);
+        self.client.request(method, &url).header(AUTH_HEADER, token)
+    }
+
+    // 发送请求；证书校验失败（包括指纹不符）单独报告
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, GotifyError> {
+        request.send().await.map_err(network_error)
+    }
+
+    async fn get(&self, endpoint: &str) -> Result<reqwest::Response, GotifyError> {
+        let request = self.request(reqwest::Method::GET, endpoint);
+        let resp = self.send(request).await?;
         Ok(resp)
     }
 
     async fn delete(&self, endpoint: &str) -> Result<(), GotifyError> {
-        let url = if endpoint.contains('?') {
-            format!("{}/{}&token={}", self.base_url, endpoint, self.token)
-        } else {
-            format!("{}/{}?token={}", self.base_url, endpoint, self.token)
-        };
-        info!("DELETE {}", url);
-        self.client
-            .delete(&url)
-            .send()
-            .await
-            .map_err(GotifyError::NetworkError)?;
+        let request = self.request(reqwest::Method::DELETE, endpoint);
+        let resp = self.send(request).await?;
+        Self::handle_response(resp).await?;
         Ok(())
     }
 
     async fn post(&self, endpoint: &str, body: &str) -> Result<reqwest::Response, GotifyError> {
-        let url = if endpoint.contains('?') {
-            format!("{}/{}&token={}", self.base_url, endpoint, self.token)
-        } else {
-            format!("{}/{}?token={}", self.base_url, endpoint, self.token)
-        };
-        info!("POST {}", url);
-        let resp = self
-            .client
-            .post(&url)
-            .body(body.to_string())
-            .send()
-            .await
-            .map_err(GotifyError::NetworkError)?;
+        let request = self
+            .request(reqwest::Method::POST, endpoint)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+        let resp = self.send(request).await?;
+        Ok(resp)
+    }
+
+    async fn put(&self, endpoint: &str, body: &str) -> Result<reqwest::Response, GotifyError> {
+        let request = self
+            .request(reqwest::Method::PUT, endpoint)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+        let resp = self.send(request).await?;
         Ok(resp)
     }
 
@@ -164,13 +655,13 @@ impl GotifyClient {
         Ok(body)
     }
 
-    pub async fn get_messages(
-        &self,
+    // 消息列表接口的查询参数
+    fn paged_endpoint(
+        path: &str,
         since: Option<u64>,
         limit: Option<u64>,
         offset: Option<u64>,
-    ) -> Result<Vec<Message>, GotifyError> {
-        let mut endpoint = "message".to_string();
+    ) -> String {
         let mut params = Vec::new();
 
         if let Some(id) = since {
@@ -183,19 +674,22 @@ impl GotifyClient {
             params.push(format!("offset={}", o));
         }
 
-        if !params.is_empty() {
-            endpoint = format!("message?{}", params.join("&"));
+        if params.is_empty() {
+            path.to_string()
+        } else {
+            format!("{}?{}", path, params.join("&"))
         }
+    }
 
+    async fn fetch_message_page(&self, endpoint: &str) -> Result<PagedMessages, GotifyError> {
         info!("Fetching messages from endpoint: {}", endpoint);
 
-        let resp = self.get(&endpoint).await?;
+        let resp = self.get(endpoint).await?;
         let body = Self::handle_response(resp).await?;
         info!("Got response body: {}", body);
 
-        let value: serde_json::Value =
+        let mut value: serde_json::Value =
             serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
-        info!("Parsed JSON: {}", value);
 
         let messages: Vec<Message> = value["messages"]
             .as_array()
@@ -207,9 +701,77 @@ impl GotifyClient {
             .iter()
             .filter_map(|m| serde_json::from_value(m.clone()).ok())
             .collect();
+        let paging: Paging = serde_json::from_value(value["paging"].take()).unwrap_or_default();
+
+        info!("Parsed {} messages, paging: {:?}", messages.len(), paging);
+        Ok(PagedMessages { messages, paging })
+    }
+
+    pub async fn get_messages(
+        &self,
+        since: Option<u64>,
+        limit: Option<u64>,
+        offset: Option<u64>,
+    ) -> Result<PagedMessages, GotifyError> {
+        let endpoint = Self::paged_endpoint("message", since, limit, offset);
+        self.fetch_message_page(&endpoint).await
+    }
+
+    /// Messages of one application, newest first, paged like [`Self::get_messages`].
+    pub async fn get_application_messages(
+        &self,
+        app_id: u64,
+        since: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<PagedMessages, GotifyError> {
+        let endpoint = Self::paged_endpoint(
+            &format!("application/{}/message", app_id),
+            since,
+            limit,
+            None,
+        );
+        self.fetch_message_page(&endpoint).await
+    }
 
-        info!("Parsed {} messages", messages.len());
-        Ok(messages)
+    /// Lazily walks every page, newest first, requesting the next page only
+    /// when the stream is polled again. `app_id` restricts the walk to one
+    /// application.
+    pub fn message_pages(
+        &self,
+        app_id: Option<u64>,
+        limit: u64,
+    ) -> impl Stream<Item = Result<PagedMessages, GotifyError>> {
+        let client = self.clone();
+        // 状态为下一页的 since；None 表示已经到达最后一页
+        stream::try_unfold(Some(None), move |since: Option<Option<u64>>| {
+            let client = client.clone();
+            async move {
+                let Some(since) = since else {
+                    return Ok(None);
+                };
+                let page = match app_id {
+                    Some(app_id) => {
+                        client
+                            .get_application_messages(app_id, since, Some(limit))
+                            .await?
+                    }
+                    None => client.get_messages(since, Some(limit), None).await?,
+                };
+                let next = page.next_since().map(Some);
+                Ok(Some((page, next)))
+            }
+        })
+    }
+
+    /// Deletes every message of one application in a single request.
+    pub async fn delete_application_messages(&self, app_id: u64) -> Result<(), GotifyError> {
+        self.delete(&format!("application/{}/message", app_id))
+            .await
+    }
+
+    /// Deletes all messages of the current user.
+    pub async fn delete_all_messages(&self) -> Result<(), GotifyError> {
+        self.delete("message").await
     }
 
     pub async fn delete_message(&self, message_id: u64) -> Result<(), GotifyError> {
@@ -218,27 +780,20 @@ impl GotifyClient {
         Ok(())
     }
 
+    /// Publishes `message` as the application owning `app_token`; the
+    /// client token of this connection cannot create messages.
     pub async fn create_message(
         &self,
-        title: &str,
-        message: &str,
-        priority: i32,
+        app_token: &str,
+        message: &NewMessage,
     ) -> Result<Message, GotifyError> {
-        #[derive(Serialize)]
-        struct Request {
-            title: String,
-            message: String,
-            priority: i32,
-        }
+        let req_body = serde_json::to_string(message).map_err(GotifyError::JsonError)?;
 
-        let req_body = serde_json::to_string(&Request {
-            title: title.to_string(),
-            message: message.to_string(),
-            priority,
-        })
-        .map_err(GotifyError::JsonError)?;
-
-        let resp = self.post("message", &req_body).await?;
+        let request = self
+            .request_with_token(reqwest::Method::POST, "message", app_token)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(req_body);
+        let resp = self.send(request).await?;
         let body = Self::handle_response(resp).await?;
         let value: serde_json::Value =
             serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
@@ -257,15 +812,291 @@ impl GotifyClient {
         Ok(apps)
     }
 
+    pub async fn create_application(
+        &self,
+        name: &str,
+        description: &str,
+        default_priority: i32,
+    ) -> Result<Application, GotifyError> {
+        // 旧版本服务器会忽略该字段，明确报错而不是静默丢失
+        if default_priority != 0 {
+            self.require(Feature::ApplicationDefaultPriority)?;
+        }
+        let req_body = serde_json::to_string(&ApplicationParams {
+            name,
+            description,
+            default_priority,
+        })
+        .map_err(GotifyError::JsonError)?;
+
+        let resp = self.post("application", &req_body).await?;
+        let body = Self::handle_response(resp).await?;
+        let app: Application = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(app)
+    }
+
+    pub async fn update_application(
+        &self,
+        app_id: u64,
+        name: &str,
+        description: &str,
+        default_priority: i32,
+    ) -> Result<Application, GotifyError> {
+        // 旧版本服务器会忽略该字段，明确报错而不是静默丢失
+        if default_priority != 0 {
+            self.require(Feature::ApplicationDefaultPriority)?;
+        }
+        let req_body = serde_json::to_string(&ApplicationParams {
+            name,
+            description,
+            default_priority,
+        })
+        .map_err(GotifyError::JsonError)?;
+
+        let resp = self
+            .put(&format!("application/{}", app_id), &req_body)
+            .await?;
+        let body = Self::handle_response(resp).await?;
+        let app: Application = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(app)
+    }
+
+    pub async fn delete_application(&self, app_id: u64) -> Result<(), GotifyError> {
+        self.delete(&format!("application/{}", app_id)).await
+    }
+
+    /// Uploads the application image as the multipart field `file`. The server
+    /// checks the file extension, so `file_name` should end in e.g. `.png`.
+    pub async fn upload_application_image(
+        &self,
+        app_id: u64,
+        file_name: &str,
+        data: Vec<u8>,
+    ) -> Result<Application, GotifyError> {
+        let part = reqwest::multipart::Part::bytes(data)
+            .file_name(file_name.to_string())
+            .mime_str(image_mime_type(file_name))
+            .map_err(network_error)?;
+        let form = reqwest::multipart::Form::new().part("file", part);
+
+        let request = self
+            .request(
+                reqwest::Method::POST,
+                &format!("application/{}/image", app_id),
+            )
+            .multipart(form);
+        let resp = self.send(request).await?;
+        let body = Self::handle_response(resp).await?;
+        let app: Application = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(app)
+    }
+
+    pub async fn delete_application_image(&self, app_id: u64) -> Result<(), GotifyError> {
+        self.delete(&format!("application/{}/image", app_id)).await
+    }
+
+    pub async fn get_clients(&self) -> Result<Vec<Client>, GotifyError> {
+        let resp = self.get("client").await?;
+        let body = Self::handle_response(resp).await?;
+        let clients: Vec<Client> = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(clients)
+    }
+
+    pub async fn create_client(&self, name: &str) -> Result<Client, GotifyError> {
+        let req_body =
+            serde_json::to_string(&ClientParams { name }).map_err(GotifyError::JsonError)?;
+        let resp = self.post("client", &req_body).await?;
+        let body = Self::handle_response(resp).await?;
+        let client: Client = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        crate::logging::register_secret(&client.token);
+        Ok(client)
+    }
+
+    /// Creates a replacement for the current client token under the same
+    /// name. The old token is left valid so the caller can store the new one
+    /// before revoking it.
+    pub async fn rotate_token(&self) -> Result<TokenRotation, GotifyError> {
+        let previous = self
+            .get_clients()
+            .await?
+            .into_iter()
+            .find(|c| c.token == self.token);
+        let name = previous
+            .as_ref()
+            .map_or("gotify-desktop", |c| c.name.as_str());
+        let created = self.create_client(name).await?;
+        Ok(TokenRotation {
+            client: self.with_token(&created.token),
+            created,
+            previous,
+        })
+    }
+
+    pub async fn update_client(&self, client_id: u64, name: &str) -> Result<Client, GotifyError> {
+        let req_body =
+            serde_json::to_string(&ClientParams { name }).map_err(GotifyError::JsonError)?;
+        let resp = self
+            .put(&format!("client/{}", client_id), &req_body)
+            .await?;
+        let body = Self::handle_response(resp).await?;
+        let client: Client = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(client)
+    }
+
+    pub async fn delete_client(&self, client_id: u64) -> Result<(), GotifyError> {
+        self.delete(&format!("client/{}", client_id)).await
+    }
+
+    pub async fn get_current_user(&self) -> Result<User, GotifyError> {
+        let resp = self.get("current/user").await?;
+        let body = Self::handle_response(resp).await?;
+        let user: User = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(user)
+    }
+
+    pub async fn change_password(&self, new_password: &str) -> Result<(), GotifyError> {
+        let req_body = serde_json::to_string(&serde_json::json!({ "pass": new_password }))
+            .map_err(GotifyError::JsonError)?;
+        let resp = self.post("current/user/password", &req_body).await?;
+        Self::handle_response(resp).await?;
+        Ok(())
+    }
+
+    pub async fn get_users(&self) -> Result<Vec<User>, GotifyError> {
+        let resp = self.get("user").await?;
+        let body = Self::handle_response(resp).await?;
+        let users: Vec<User> = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(users)
+    }
+
+    pub async fn get_user(&self, user_id: u64) -> Result<User, GotifyError> {
+        let resp = self.get(&format!("user/{}", user_id)).await?;
+        let body = Self::handle_response(resp).await?;
+        let user: User = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(user)
+    }
+
+    pub async fn create_user(
+        &self,
+        name: &str,
+        password: &str,
+        admin: bool,
+    ) -> Result<User, GotifyError> {
+        let req_body = serde_json::to_string(&UserParams {
+            name,
+            pass: Some(password),
+            admin,
+        })
+        .map_err(GotifyError::JsonError)?;
+        let resp = self.post("user", &req_body).await?;
+        let body = Self::handle_response(resp).await?;
+        let user: User = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(user)
+    }
+
+    /// Updates a user; the password is left unchanged when `password` is `None`.
+    /// Gotify uses `POST` rather than `PUT` for this endpoint.
+    pub async fn update_user(
+        &self,
+        user_id: u64,
+        name: &str,
+        password: Option<&str>,
+        admin: bool,
+    ) -> Result<User, GotifyError> {
+        let req_body = serde_json::to_string(&UserParams {
+            name,
+            pass: password.filter(|p| !p.is_empty()),
+            admin,
+        })
+        .map_err(GotifyError::JsonError)?;
+        let resp = self.post(&format!("user/{}", user_id), &req_body).await?;
+        let body = Self::handle_response(resp).await?;
+        let user: User = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(user)
+    }
+
+    pub async fn delete_user(&self, user_id: u64) -> Result<(), GotifyError> {
+        self.delete(&format!("user/{}", user_id)).await
+    }
+
+    pub async fn get_plugins(&self) -> Result<Vec<Plugin>, GotifyError> {
+        self.require(Feature::Plugins)?;
+        let resp = self.get("plugin").await?;
+        let body = Self::handle_response(resp).await?;
+        let plugins: Vec<Plugin> = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(plugins)
+    }
+
+    pub async fn set_plugin_enabled(
+        &self,
+        plugin_id: u64,
+        enabled: bool,
+    ) -> Result<(), GotifyError> {
+        self.require(Feature::Plugins)?;
+        let action = if enabled { "enable" } else { "disable" };
+        let request = self.request(
+            reqwest::Method::POST,
+            &format!("plugin/{}/{}", plugin_id, action),
+        );
+        let resp = self.send(request).await?;
+        Self::handle_response(resp).await?;
+        Ok(())
+    }
+
+    /// Returns the plugin configuration as YAML. Only plugins with the
+    /// `configurer` capability have one.
+    pub async fn get_plugin_config(&self, plugin_id: u64) -> Result<String, GotifyError> {
+        self.require(Feature::Plugins)?;
+        let resp = self.get(&format!("plugin/{}/config", plugin_id)).await?;
+        Self::handle_response(resp).await
+    }
+
+    pub async fn update_plugin_config(
+        &self,
+        plugin_id: u64,
+        yaml: &str,
+    ) -> Result<(), GotifyError> {
+        self.require(Feature::Plugins)?;
+        let request = self
+            .request(
+                reqwest::Method::POST,
+                &format!("plugin/{}/config", plugin_id),
+            )
+            .header(reqwest::header::CONTENT_TYPE, "application/x-yaml")
+            .body(yaml.to_string());
+        let resp = self.send(request).await?;
+        Self::handle_response(resp).await?;
+        Ok(())
+    }
+
+    /// Returns the markdown a `displayer` plugin renders for the user.
+    pub async fn get_plugin_display(&self, plugin_id: u64) -> Result<String, GotifyError> {
+        self.require(Feature::Plugins)?;
+        let resp = self.get(&format!("plugin/{}/display", plugin_id)).await?;
+        let body = Self::handle_response(resp).await?;
+        // 服务器返回 JSON 编码的字符串
+        let display: String = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        Ok(display)
+    }
+
+    /// Fetches `/version` and remembers it for feature checks.
+    pub async fn get_version(&self) -> Result<VersionInfo, GotifyError> {
+        let resp = self.get("version").await?;
+        let body = Self::handle_response(resp).await?;
+        let version: VersionInfo = serde_json::from_str(&body).map_err(GotifyError::JsonError)?;
+        info!(
+            "Server {} runs Gotify {} ({})",
+            self.base_url, version.version, version.commit
+        );
+        self.set_server_version(version.clone());
+        Ok(version)
+    }
+
     pub async fn get_health(&self) -> Result<bool, GotifyError> {
-        let url = format!("{}/health", self.base_url);
+        let url = self.url("health");
         info!("GET {}", url);
-        let resp = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .map_err(GotifyError::NetworkError)?;
+        let request = self.client.get(&url);
+        let resp = self.send(request).await?;
         Ok(resp.status().is_success())
     }
 }
@@ -0,0 +1,74 @@
+use regex::Regex;
+use std::io::Write;
+use std::sync::{OnceLock, RwLock};
+
+const MASK: &str = "***";
+
+// 已知的密钥（客户端令牌等），日志中出现时一律替换
+fn secrets() -> &'static RwLock<Vec<String>> {
+    static SECRETS: OnceLock<RwLock<Vec<String>>> = OnceLock::new();
+    SECRETS.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+// 常见的令牌写法：查询参数、请求头、JSON 字段和 Bearer 认证
+fn patterns() -> &'static [Regex] {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            r#"(?i)(\btoken=)[^&\s"'#]+"#,
+            r#"(?i)(x-gotify-key["']?\s*[:=]\s*["']?)[^\s"',}]+"#,
+            r#"(?i)("token"\s*:\s*")[^"]*"#,
+            r#"(?i)(\bbearer\s+)[^\s"',}]+"#,
+        ]
+        .iter()
+        .map(|p| Regex::new(p).expect("invalid redaction pattern"))
+        .collect()
+    })
+}
+
+/// Registers a secret that must never appear in log output.
+pub fn register_secret(secret: &str) {
+    // 过短的值替换后会误伤正常日志内容
+    if secret.len() < 4 {
+        return;
+    }
+    let mut secrets = secrets().write().unwrap();
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// Masks tokens and other credentials in `text`.
+pub fn redact(text: &str) -> String {
+    let mut redacted = text.to_string();
+    for pattern in patterns() {
+        redacted = pattern
+            .replace_all(&redacted, |caps: &regex::Captures| {
+                format!("{}{}", &caps[1], MASK)
+            })
+            .into_owned();
+    }
+    for secret in secrets().read().unwrap().iter() {
+        redacted = redacted.replace(secret.as_str(), MASK);
+    }
+    redacted
+}
+
+/// Initializes `env_logger` with every record passed through [`redact`].
+pub fn init(level_filter: log::LevelFilter) {
+    env_logger::Builder::from_default_env()
+        .filter_level(level_filter)
+        .filter_module("chromium", log::LevelFilter::Error) // 抑制 Chromium 的非错误日志
+        .filter_module("ui::gfx", log::LevelFilter::Error) // 抑制 UI/GFX 的 INFO 日志
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {} {}] {}",
+                buf.timestamp(),
+                record.level(),
+                record.target(),
+                redact(&record.args().to_string())
+            )
+        })
+        .init();
+}
@@ -1,14 +1,29 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod connections;
+mod endpoint;
 mod gotify;
+mod logging;
+mod proxy;
+mod store;
+mod stream;
 mod tests;
-
-use crate::gotify::GotifyClient;
-use futures_util::StreamExt;
-use log::{debug, error, info, warn};
+mod tls;
+mod vault;
+
+use crate::connections::ConnectionManager;
+use crate::gotify::{BasicAuth, ClientOptions, ConnectionUpdate, GotifyClient};
+use crate::proxy::ProxyOptions;
+use crate::store::{
+    FlagUpdate, LocalMessageQuery, MessageStore, SearchQuery, StoredMessage, UnreadCount,
+};
+use crate::stream::{ConnectionState, ServerStatus, StreamEvents, StreamInfo, StreamSettings};
+use crate::tls::TlsOptions;
+use crate::vault::{VaultError, VaultMode, VaultSettings};
+use log::{debug, error, info};
+use std::collections::BTreeMap;
 use std::sync::Mutex;
 use tauri::{Emitter, Manager, State};
-use tokio::sync::mpsc;
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ApiResponse<T> {
@@ -43,9 +58,10 @@ impl<T> ApiResponse<T> {
 }
 
 struct AppState {
-    client: Mutex<Option<GotifyClient>>,
-    message_tx: Mutex<Option<mpsc::UnboundedSender<gotify::Message>>>,
+    // 按服务器标识（ServerConfig.id）管理的所有连接
+    connections: ConnectionManager,
     settings: Mutex<AppSettings>,
+    store: MessageStore,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
@@ -61,37 +77,41 @@ pub struct AppSettings {
     pub silent_start: bool,
     pub enable_notifications: bool,
     pub log_level: String,
+    #[serde(default)]
+    pub stream: StreamSettings,
+    // 全局代理，服务器可单独覆盖
+    #[serde(default)]
+    pub proxy: ProxyOptions,
+    // 令牌的保存方式，只能通过 configure_vault 修改
+    #[serde(default)]
+    pub vault: VaultSettings,
 }
 
 impl AppState {
     fn new() -> Self {
         Self {
-            client: Mutex::new(None),
-            message_tx: Mutex::new(None),
+            connections: ConnectionManager::new(),
             settings: Mutex::new(AppSettings::default()),
+            store: MessageStore::open_or_memory(&get_store_path()),
         }
     }
 
-    fn set_client(&self, mut client: GotifyClient, tx: mpsc::UnboundedSender<gotify::Message>) {
-        client.set_message_sender(tx.clone());
-        *self.client.lock().unwrap() = Some(client);
-        *self.message_tx.lock().unwrap() = Some(tx);
-        info!("Gotify client initialized");
+    fn cache_applications(&self, server_id: &str, apps: &[gotify::Application]) {
+        if let Err(e) = self.store.save_applications(server_id, apps) {
+            error!("Failed to cache applications locally: {}", e);
+        }
     }
 
-    fn clear_client(&self) {
-        *self.client.lock().unwrap() = None;
-        *self.message_tx.lock().unwrap() = None;
-        info!("Gotify client cleared");
+    // 写入本地消息库，失败只记录日志，不影响消息投递
+    fn cache_messages(&self, server_id: &str, messages: &[gotify::Message]) {
+        if let Err(e) = self.store.save_messages(server_id, messages) {
+            error!("Failed to cache messages locally: {}", e);
+        }
     }
 
-    fn get_client(&self) -> Result<GotifyClient, String> {
-        self.client
-            .lock()
-            .unwrap()
-            .as_ref()
-            .cloned()
-            .ok_or_else(|| "Not connected to Gotify server. Please connect first.".to_string())
+    // 返回 (服务器标识, 客户端)；未指定服务器时使用唯一的已连接服务器
+    fn get_client(&self, server_id: Option<&str>) -> Result<(String, GotifyClient), String> {
+        self.connections.client(server_id)
     }
 
     fn get_settings(&self) -> AppSettings {
@@ -107,6 +127,78 @@ impl AppState {
 struct ConnectRequest {
     server_url: String,
     token: String,
+    // 已保存配置的 id；临时连接时为空，使用服务器地址作为本地缓存的键
+    #[serde(default)]
+    server_id: Option<String>,
+    // 为空时使用已保存配置中的 TLS 和代理设置
+    #[serde(default)]
+    tls: Option<TlsOptions>,
+    #[serde(default)]
+    proxy: Option<ProxyOptions>,
+}
+
+// 账号密码登录；密码只用于创建客户端令牌，不会写入配置或日志
+#[derive(serde::Deserialize)]
+struct LoginRequest {
+    server_url: String,
+    username: String,
+    password: String,
+    #[serde(default)]
+    name: Option<String>,
+    // 已有配置的 id；为空时新建配置
+    #[serde(default)]
+    server_id: Option<String>,
+    #[serde(default)]
+    tls: Option<TlsOptions>,
+    // 代理、请求头和基本认证：未提供时沿用已有配置
+    #[serde(flatten)]
+    connection: ConnectionUpdate,
+}
+
+// 以指定应用的身份发送消息
+#[derive(serde::Deserialize, Debug)]
+struct CreateMessageRequest {
+    app_id: u64,
+    #[serde(default)]
+    title: Option<String>,
+    message: String,
+    // 为空时使用应用的默认优先级
+    #[serde(default)]
+    priority: Option<i32>,
+    #[serde(default)]
+    markdown: bool,
+    #[serde(default)]
+    click_url: Option<String>,
+    #[serde(default)]
+    big_image_url: Option<String>,
+    // 其它自定义 extras，上面的选项会合并进去
+    #[serde(default)]
+    extras: Option<serde_json::Value>,
+}
+
+impl CreateMessageRequest {
+    fn to_message(&self) -> gotify::NewMessage {
+        let mut message = gotify::NewMessage::new(&self.message);
+        if let Some(title) = self.title.as_deref().filter(|t| !t.is_empty()) {
+            message = message.title(title);
+        }
+        if let Some(priority) = self.priority {
+            message = message.priority(priority);
+        }
+        if let Some(extras) = &self.extras {
+            message = message.extras(extras.clone());
+        }
+        if self.markdown {
+            message = message.markdown();
+        }
+        if let Some(url) = self.click_url.as_deref().filter(|u| !u.is_empty()) {
+            message = message.click_url(url);
+        }
+        if let Some(url) = self.big_image_url.as_deref().filter(|u| !u.is_empty()) {
+            message = message.big_image_url(url);
+        }
+        message
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -116,6 +208,88 @@ pub struct ServerConfig {
     pub server_url: String,
     pub token: String,
     pub last_used: Option<String>,
+    // 启用的服务器会在启动时同时连接
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // 最近一次连接时检测到的服务器版本
+    #[serde(default)]
+    pub server_version: Option<String>,
+    #[serde(default)]
+    pub server_commit: Option<String>,
+    // 自定义 CA、证书指纹和客户端证书
+    #[serde(default)]
+    pub tls: TlsOptions,
+    // 为空时使用全局代理设置
+    #[serde(default)]
+    pub proxy: Option<ProxyOptions>,
+    // 反向代理要求的额外请求头和 HTTP 基本认证
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub basic_auth: Option<BasicAuth>,
+}
+
+impl ServerConfig {
+    fn client(&self, settings: &AppSettings) -> Result<GotifyClient, gotify::GotifyError> {
+        // 保险库未解锁时配置中只有令牌的引用
+        if vault::reference_id(&self.token).is_some() {
+            return Err(gotify::GotifyError::AuthFailed(
+                VaultError::Locked.to_string(),
+            ));
+        }
+        let options = client_options(Some(self), None, None, settings);
+        let client = GotifyClient::with_options(&self.server_url, &self.token, &options)?;
+        self.seed_version(&client);
+        Ok(client)
+    }
+
+    // 先使用上次检测到的版本判断功能是否可用，连接后再重新检测
+    fn seed_version(&self, client: &GotifyClient) {
+        if let Some(version) = &self.server_version {
+            client.set_server_version(gotify::VersionInfo {
+                version: version.clone(),
+                commit: self.server_commit.clone().unwrap_or_default(),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+// 连接参数优先取请求中的设置，其次是已保存的配置；服务器没有单独设置代理时使用全局代理
+fn client_options(
+    config: Option<&ServerConfig>,
+    tls: Option<TlsOptions>,
+    proxy: Option<ProxyOptions>,
+    settings: &AppSettings,
+) -> ClientOptions {
+    ClientOptions {
+        tls: tls
+            .or_else(|| config.map(|c| c.tls.clone()))
+            .unwrap_or_default(),
+        proxy: proxy
+            .or_else(|| config.and_then(|c| c.proxy.clone()))
+            .unwrap_or_else(|| settings.proxy.clone()),
+        headers: config.map(|c| c.headers.clone()).unwrap_or_default(),
+        basic_auth: config.and_then(|c| c.basic_auth.clone()),
+    }
+}
+
+// 保存前检查请求头能否用于请求，避免保存后每次连接都失败
+fn check_headers(
+    headers: &BTreeMap<String, String>,
+    basic_auth: &Option<BasicAuth>,
+) -> Result<(), gotify::GotifyError> {
+    ClientOptions {
+        headers: headers.clone(),
+        basic_auth: basic_auth.clone(),
+        ..Default::default()
+    }
+    .header_map()
+    .map(|_| ())
+}
+
+fn default_enabled() -> bool {
+    true
 }
 
 #[derive(serde::Deserialize)]
@@ -124,6 +298,13 @@ struct UpdateConfigRequest {
     name: String,
     server_url: String,
     token: String,
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    tls: Option<TlsOptions>,
+    // 未提供的字段保持不变；代理为 null 表示使用全局代理
+    #[serde(flatten)]
+    connection: ConnectionUpdate,
 }
 
 fn generate_id() -> String {
@@ -139,6 +320,8 @@ fn generate_id() -> String {
 
 const APP_CONFIG_DIR: &str = ".gotify-desktop";
 const CONFIG_FILE: &str = "config.json";
+const STORE_FILE: &str = "messages.db";
+const VAULT_FILE: &str = "tokens.vault";
 
 fn get_config_dir() -> std::path::PathBuf {
     let mut path = dirs::home_dir().unwrap_or_else(|| std::path::PathBuf::from("."));
@@ -160,11 +343,25 @@ fn get_config_path() -> std::path::PathBuf {
     path
 }
 
+fn get_vault_path() -> std::path::PathBuf {
+    let mut path = get_config_dir();
+    path.push(VAULT_FILE);
+    path
+}
+
+fn get_store_path() -> std::path::PathBuf {
+    let mut path = get_config_dir();
+    path.push(STORE_FILE);
+    path
+}
+
 fn load_app_config() -> AppConfig {
     let config_path = get_config_path();
     if config_path.exists() {
         let content = std::fs::read_to_string(&config_path).unwrap_or_default();
-        serde_json::from_str(&content).unwrap_or_default()
+        let mut config: AppConfig = serde_json::from_str(&content).unwrap_or_default();
+        open_tokens(&mut config);
+        config
     } else {
         AppConfig::default()
     }
@@ -182,13 +379,49 @@ fn save_app_config(config: &AppConfig) -> Result<(), String> {
         }
     }
 
-    let json = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
-    std::fs::write(&config_path, json).map_err(|e| {
+    let mut config = config.clone();
+    seal_tokens(&mut config).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+    // 配置中可能包含令牌和密码，只允许当前用户读写
+    vault::write_private(&config_path, json.as_bytes()).map_err(|e| {
         error!("Failed to write config: {}", e);
         e.to_string()
     })
 }
 
+// 保险库已解锁时，把配置中的令牌引用替换为实际令牌
+fn open_tokens(config: &mut AppConfig) {
+    if let Some(vault) = vault::unlocked() {
+        if let Err(e) = vault.open_tokens(config.servers.iter_mut().map(|s| &mut s.token)) {
+            error!("Failed to read token vault: {}", e);
+        }
+    }
+}
+
+// 启用保险库时令牌写入保险库，配置文件中只保留引用
+fn seal_tokens(config: &mut AppConfig) -> Result<(), VaultError> {
+    if config.settings.vault.mode == VaultMode::Plaintext {
+        return Ok(());
+    }
+    match vault::unlocked() {
+        Some(vault) => vault.seal(
+            config
+                .servers
+                .iter_mut()
+                .map(|s| (s.id.as_str(), &mut s.token)),
+        ),
+        // 未解锁时只能保存仍是引用的令牌，例如修改其他设置
+        None if config
+            .servers
+            .iter()
+            .all(|s| s.token.is_empty() || vault::reference_id(&s.token).is_some()) =>
+        {
+            Ok(())
+        }
+        None => Err(VaultError::Locked),
+    }
+}
+
 // 为了向后兼容，保留这些函数但它们现在操作统一配置
 fn load_settings() -> AppSettings {
     load_app_config().settings
@@ -216,7 +449,15 @@ async fn save_config(
     name: String,
     server_url: String,
     token: String,
+    tls: Option<TlsOptions>,
+    proxy: Option<ProxyOptions>,
+    headers: Option<BTreeMap<String, String>>,
+    basic_auth: Option<BasicAuth>,
 ) -> Result<ApiResponse<()>, String> {
+    let headers = headers.unwrap_or_default();
+    if let Err(e) = check_headers(&headers, &basic_auth) {
+        return Ok(ApiResponse::error(e.to_string()));
+    }
     let mut configs = load_configs();
 
     let now = chrono::Utc::now().to_rfc3339();
@@ -227,6 +468,13 @@ async fn save_config(
         server_url,
         token,
         last_used: Some(now),
+        enabled: true,
+        server_version: None,
+        server_commit: None,
+        tls: tls.unwrap_or_default(),
+        proxy,
+        headers,
+        basic_auth,
     };
 
     configs.push(new_config);
@@ -245,19 +493,25 @@ async fn get_configs(
 
 #[tauri::command]
 async fn delete_config(
-    _app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
     id: String,
 ) -> Result<ApiResponse<()>, String> {
     let mut configs = load_configs();
     configs.retain(|c| c.id != id);
     save_configs(&configs)?;
 
+    if state.connections.disconnect(&id) {
+        emit_connection_status(&app_handle, &id, &ConnectionState::Disconnected);
+    }
+
     Ok(ApiResponse::success(()))
 }
 
 #[tauri::command]
 async fn update_config(
-    _app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
     req: UpdateConfigRequest,
 ) -> Result<ApiResponse<()>, String> {
     let mut configs = load_configs();
@@ -265,14 +519,38 @@ async fn update_config(
     // 更新配置
     if let Some(config) = configs.iter_mut().find(|c| c.id == req.id) {
         config.name = req.name;
+        // 地址变化后旧的版本信息不再可信，下次连接时重新检测
+        if config.server_url != req.server_url {
+            config.server_version = None;
+            config.server_commit = None;
+        }
         config.server_url = req.server_url;
         config.token = req.token;
+        if let Some(enabled) = req.enabled {
+            config.enabled = enabled;
+        }
+        if let Some(tls) = req.tls {
+            config.tls = tls;
+        }
+        req.connection.apply(
+            &mut config.proxy,
+            &mut config.headers,
+            &mut config.basic_auth,
+        );
+        if let Err(e) = check_headers(&config.headers, &config.basic_auth) {
+            return Ok(ApiResponse::error(e.to_string()));
+        }
     } else {
         return Err("配置不存在".to_string());
     }
 
     save_configs(&configs)?;
 
+    // 停用的服务器立即断开
+    if req.enabled == Some(false) && state.connections.disconnect(&req.id) {
+        emit_connection_status(&app_handle, &req.id, &ConnectionState::Disconnected);
+    }
+
     Ok(ApiResponse::success(()))
 }
 
@@ -309,116 +587,200 @@ async fn get_default_config(
     Ok(ApiResponse::success(last_config))
 }
 
-async fn start_websocket_listener(app_handle: tauri::AppHandle, base_url: String, token: String) {
-    info!("Starting WebSocket task...");
-    let ws_url = format!(
-        "{}/stream?token={}",
-        base_url
-            .replace("http://", "ws://")
-            .replace("https://", "wss://"),
-        token
-    );
-    info!("Connecting to WebSocket: {}", ws_url);
-
-    loop {
-        match tokio_tungstenite::connect_async(&ws_url).await {
-            Ok((ws_stream, response)) => {
-                info!("WebSocket connected, status: {:?}", response.status());
-                let (_, mut read) = ws_stream.split();
-
-                while let Some(result) = read.next().await {
-                    match result {
-                        Ok(msg) => {
-                            if msg.is_text() {
-                                let text = msg.to_text().unwrap_or("");
-                                info!("WebSocket received text: {}", text);
-                                if let Ok(value) = serde_json::from_str::<serde_json::Value>(text) {
-                                    info!("Parsed JSON: {:?}", value);
-                                    if let Some(msg_obj) = value.as_object() {
-                                        if let Ok(message) = serde_json::from_value::<gotify::Message>(
-                                            serde_json::Value::Object(msg_obj.clone()),
-                                        ) {
-                                            info!(
-                                                "Received message via WebSocket: id={}",
-                                                message.id
-                                            );
-                                            if let Err(e) = app_handle.emit("new-message", &message)
-                                            {
-                                                error!("Failed to emit message event: {}", e);
-                                            }
-                                        } else {
-                                            warn!("Failed to parse message from JSON");
-                                        }
-                                    }
-                                } else {
-                                    warn!("Failed to parse JSON from: {}", text);
-                                }
-                            } else if msg.is_close() {
-                                info!("WebSocket close message received");
-                                break;
-                            }
-                        }
-                        Err(e) => {
-                            error!("WebSocket error: {}", e);
-                            break;
-                        }
-                    }
-                }
-                info!("WebSocket connection closed, reconnecting in 5 seconds...");
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+// 消息管道的处理与投递阶段：每条去重后的消息只产生一次前端事件和一次系统通知
+struct FrontendEvents {
+    app_handle: tauri::AppHandle,
+    server_id: String,
+    client: GotifyClient,
+}
+
+impl StreamEvents for FrontendEvents {
+    fn on_message(&self, mut message: gotify::Message) {
+        message.server_id = Some(self.server_id.clone());
+        let state = self.app_handle.state::<AppState>();
+        state.cache_messages(&self.server_id, std::slice::from_ref(&message));
+        emit_unread_count(&self.app_handle);
+
+        info!(
+            "Delivering message to frontend: server={}, id={}",
+            self.server_id, message.id
+        );
+        if let Err(e) = self.app_handle.emit("new-message", &message) {
+            error!("Failed to emit message event: {}", e);
+        }
+
+        if state.get_settings().enable_notifications {
+            use tauri_plugin_notification::NotificationExt;
+            if let Err(e) = self
+                .app_handle
+                .notification()
+                .builder()
+                .title(message.title.as_deref().unwrap_or("新消息"))
+                .body(&message.message)
+                .show()
+            {
+                error!("Failed to show notification: {}", e);
             }
-            Err(e) => {
-                error!(
-                    "WebSocket connection failed: {}, retrying in 5 seconds...",
-                    e
-                );
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+        }
+    }
+
+    fn on_status(&self, status: &ConnectionState) {
+        emit_connection_status(&self.app_handle, &self.server_id, status);
+        // 每次连上（包括重连）都重新检测版本，服务器升级后功能判断随之更新
+        if *status == ConnectionState::Connected {
+            let client = self.client.clone();
+            let server_id = self.server_id.clone();
+            tokio::spawn(async move {
+                match client.get_version().await {
+                    Ok(version) => remember_server_version(&server_id, &version),
+                    Err(e) => error!("Failed to detect version of {}: {}", server_id, e),
+                }
+            });
+        }
+    }
+}
+
+// 通知前端某台服务器的连接状态变化，并同步更新托盘提示
+fn emit_connection_status(
+    app_handle: &tauri::AppHandle,
+    server_id: &str,
+    status: &ConnectionState,
+) {
+    let payload = ServerStatus {
+        server_id: server_id.to_string(),
+        status: status.clone(),
+    };
+    if let Err(e) = app_handle.emit("connection-status", &payload) {
+        error!("Failed to emit connection status event: {}", e);
+    }
+    refresh_tray_tooltip(app_handle);
+}
+
+// 本地消息状态变化后通知前端最新的未读数
+fn emit_unread_count(app_handle: &tauri::AppHandle) {
+    match app_handle.state::<AppState>().store.unread_count() {
+        Ok(count) => {
+            if let Err(e) = app_handle.emit("unread-count", &count) {
+                error!("Failed to emit unread count event: {}", e);
             }
         }
+        Err(e) => error!("Failed to count unread messages: {}", e),
+    }
+    refresh_tray_tooltip(app_handle);
+}
+
+// 托盘提示显示连接状态和未读数
+fn refresh_tray_tooltip(app_handle: &tauri::AppHandle) {
+    let Some(tray) = app_handle.tray_by_id("main-tray") else {
+        return;
+    };
+    let state = app_handle.state::<AppState>();
+    let statuses = state.connections.statuses();
+    let mut tooltip = format!(
+        "Gotify Desktop - {}",
+        ConnectionState::summary_label(statuses.values())
+    );
+    if let Ok(count) = state.store.unread_count() {
+        if count.total > 0 {
+            tooltip.push_str(&format!("（{} 条未读）", count.total));
+        }
+    }
+    if let Err(e) = tray.set_tooltip(Some(tooltip)) {
+        error!("Failed to update tray tooltip: {}", e);
+    }
+}
+
+// 将检测到的版本写入对应的服务器配置，临时连接没有配置则忽略
+fn remember_server_version(server_id: &str, version: &gotify::VersionInfo) {
+    let mut configs = load_configs();
+    let Some(config) = configs.iter_mut().find(|c| c.id == server_id) else {
+        return;
+    };
+    if config.server_version.as_deref() == Some(version.version.as_str())
+        && config.server_commit.as_deref() == Some(version.commit.as_str())
+    {
+        return;
     }
+    config.server_version = Some(version.version.clone());
+    config.server_commit = Some(version.commit.clone());
+    if let Err(e) = save_configs(&configs) {
+        error!("Failed to save server version: {}", e);
+    }
+}
+
+// 连接时同步的最近消息数量
+const SYNC_MESSAGE_LIMIT: u64 = 100;
+
+// 连接单台服务器：启动消息流，并在后台缓存应用名称和最近的消息
+fn start_connection(app_handle: &tauri::AppHandle, server_id: &str, client: GotifyClient) {
+    let state = app_handle.state::<AppState>();
+    state.connections.connect(
+        server_id,
+        client.clone(),
+        state.get_settings().stream,
+        FrontendEvents {
+            app_handle: app_handle.clone(),
+            server_id: server_id.to_string(),
+            client: client.clone(),
+        },
+    );
+
+    let app = app_handle.clone();
+    let server_id = server_id.to_string();
+    tokio::spawn(async move {
+        let state = app.state::<AppState>();
+        // 应用名称供本地搜索按应用名匹配
+        match client.get_applications().await {
+            Ok(apps) => state.cache_applications(&server_id, &apps),
+            Err(e) => error!("Failed to fetch applications of {}: {}", server_id, e),
+        }
+        // 最近的消息进入本地库，统一收件箱才能包含每台服务器的历史
+        match client
+            .get_messages(None, Some(SYNC_MESSAGE_LIMIT), None)
+            .await
+        {
+            Ok(page) => {
+                state.cache_messages(&server_id, &page.messages);
+                emit_unread_count(&app);
+            }
+            Err(e) => error!("Failed to sync messages of {}: {}", server_id, e),
+        }
+    });
 }
 
 #[tauri::command]
 async fn connect_to_gotify(
-    state: State<'_, AppState>,
     app_handle: tauri::AppHandle,
     req: ConnectRequest,
-) -> Result<ApiResponse<()>, String> {
+) -> Result<ApiResponse<String>, String> {
     debug!(
         "Attempting to connect to Gotify server at: {}",
         req.server_url
     );
 
-    match GotifyClient::new(&req.server_url, &req.token) {
-        Ok(mut client) => {
+    if vault::reference_id(&req.token).is_some() {
+        return Ok(ApiResponse::error(VaultError::Locked.to_string()));
+    }
+    let config = req
+        .server_id
+        .as_deref()
+        .and_then(|id| load_configs().into_iter().find(|c| c.id == id));
+    let settings = app_handle.state::<AppState>().get_settings();
+    let options = client_options(config.as_ref(), req.tls, req.proxy, &settings);
+    match GotifyClient::with_options(&req.server_url, &req.token, &options) {
+        Ok(client) => {
             info!("GotifyClient created successfully");
+            if let Some(config) = config.as_ref().filter(|c| c.server_url == req.server_url) {
+                config.seed_version(&client);
+            }
+            let server_id = req
+                .server_id
+                .unwrap_or_else(|| client.get_base_url().to_string());
 
-            let (tx, mut rx) = mpsc::unbounded_channel::<gotify::Message>();
-            client.set_message_sender(tx.clone());
-            info!("Message sender set");
-
-            let base_url = client.get_base_url().to_string();
-            let token = client.get_token().to_string();
-
-            let app_clone = app_handle.clone();
-            tokio::spawn(async move {
-                start_websocket_listener(app_clone, base_url, token).await;
-            });
-
-            state.set_client(client, tx);
-            info!("Client saved to state");
-
-            let app = app_handle.clone();
-            tokio::spawn(async move {
-                while let Some(message) = rx.recv().await {
-                    info!("Forwarding message to frontend: id={}", message.id);
-                    if let Err(e) = app.emit("new-message", message) {
-                        error!("Failed to emit message event: {}", e);
-                    }
-                }
-            });
+            // 只替换该服务器原有的连接，其他服务器保持连接
+            start_connection(&app_handle, &server_id, client);
 
-            Ok(ApiResponse::success(()))
+            Ok(ApiResponse::success(server_id))
         }
         Err(e) => {
             let error_msg = format!("Failed to connect to Gotify server: {}", e);
@@ -428,25 +790,195 @@ async fn connect_to_gotify(
     }
 }
 
+// 登录时创建的客户端令牌名称，便于在服务器上识别设备
+fn login_client_name() -> String {
+    format!(
+        "gotify-desktop on {}",
+        gethostname::gethostname().to_string_lossy()
+    )
+}
+
+// 使用账号密码登录：创建客户端令牌并保存到配置，随后连接该服务器
+#[tauri::command]
+async fn login_to_gotify(
+    app_handle: tauri::AppHandle,
+    req: LoginRequest,
+) -> Result<ApiResponse<ServerConfig>, String> {
+    let LoginRequest {
+        server_url,
+        username,
+        password,
+        name,
+        server_id,
+        tls,
+        connection,
+    } = req;
+
+    let mut configs = load_configs();
+    let settings = app_handle.state::<AppState>().get_settings();
+    let existing = server_id
+        .as_deref()
+        .and_then(|id| configs.iter().find(|c| c.id == id));
+    let (mut proxy, mut headers, mut basic_auth) = existing
+        .map(|c| (c.proxy.clone(), c.headers.clone(), c.basic_auth.clone()))
+        .unwrap_or_default();
+    connection.apply(&mut proxy, &mut headers, &mut basic_auth);
+    let options = ClientOptions {
+        proxy: proxy.clone().unwrap_or_else(|| settings.proxy.clone()),
+        headers,
+        basic_auth,
+        ..client_options(existing, tls, None, &settings)
+    };
+    let created = match GotifyClient::login(
+        &server_url,
+        &username,
+        &password,
+        &login_client_name(),
+        &options,
+    )
+    .await
+    {
+        Ok(created) => created,
+        Err(e) => {
+            error!("Login to {} failed: {}", server_url, e);
+            return Ok(ApiResponse::error(e.to_string()));
+        }
+    };
+    info!("Created client token {} for {}", created.name, server_url);
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let existing = server_id
+        .as_deref()
+        .and_then(|id| configs.iter_mut().find(|c| c.id == id));
+    let config = match existing {
+        Some(config) => {
+            config.server_url = server_url;
+            config.token = created.token;
+            config.tls = options.tls;
+            config.proxy = proxy;
+            config.headers = options.headers;
+            config.basic_auth = options.basic_auth;
+            if let Some(name) = name {
+                config.name = name;
+            }
+            config.last_used = Some(now);
+            config.clone()
+        }
+        None => {
+            let config = ServerConfig {
+                id: generate_id(),
+                name: name.unwrap_or_else(|| server_url.clone()),
+                server_url,
+                token: created.token,
+                last_used: Some(now),
+                enabled: true,
+                server_version: None,
+                server_commit: None,
+                tls: options.tls,
+                proxy,
+                headers: options.headers,
+                basic_auth: options.basic_auth,
+            };
+            configs.push(config.clone());
+            config
+        }
+    };
+    let client = config.client(&settings);
+    if let Err(e) = save_configs(&configs) {
+        if let Ok(client) = &client {
+            revoke_unsaved_token(client, created.id).await;
+        }
+        return Err(e);
+    }
+
+    match client {
+        Ok(client) => start_connection(&app_handle, &config.id, client),
+        Err(e) => return Ok(ApiResponse::error(e.to_string())),
+    }
+    Ok(ApiResponse::success(config))
+}
+
+// 新令牌已在服务器上创建但配置没能保存，撤销它以免留下无人使用的客户端
+async fn revoke_unsaved_token(client: &GotifyClient, client_id: u64) {
+    if let Err(e) = client.delete_client(client_id).await {
+        error!("Failed to revoke unsaved client token: {}", e);
+    }
+}
+
+// 同时连接所有启用的服务器，返回已连接的服务器标识
+#[tauri::command]
+async fn connect_all_servers(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<ApiResponse<Vec<String>>, String> {
+    for config in load_configs().into_iter().filter(|c| c.enabled) {
+        if state.connections.is_connected(&config.id) {
+            continue;
+        }
+        match config.client(&state.get_settings()) {
+            Ok(client) => start_connection(&app_handle, &config.id, client),
+            Err(e) => error!("Failed to connect to {}: {}", config.name, e),
+        }
+    }
+    Ok(ApiResponse::success(state.connections.server_ids()))
+}
+
+// 启用或停用服务器，立即连接或断开
+#[tauri::command]
+async fn set_server_enabled(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    id: String,
+    enabled: bool,
+) -> Result<ApiResponse<()>, String> {
+    let mut configs = load_configs();
+    let Some(config) = configs.iter_mut().find(|c| c.id == id) else {
+        return Err("配置不存在".to_string());
+    };
+    config.enabled = enabled;
+    let config = config.clone();
+    save_configs(&configs)?;
+
+    if enabled {
+        if !state.connections.is_connected(&id) {
+            match config.client(&state.get_settings()) {
+                Ok(client) => start_connection(&app_handle, &id, client),
+                Err(e) => return Ok(ApiResponse::error(e.to_string())),
+            }
+        }
+    } else if state.connections.disconnect(&id) {
+        emit_connection_status(&app_handle, &id, &ConnectionState::Disconnected);
+    }
+
+    Ok(ApiResponse::success(()))
+}
+
 #[tauri::command]
 async fn fetch_messages(
     state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    server_id: Option<String>,
     since: Option<u64>,
     limit: Option<u64>,
     offset: Option<u64>,
-) -> Result<ApiResponse<Vec<gotify::Message>>, String> {
+) -> Result<ApiResponse<gotify::PagedMessages>, String> {
     debug!(
-        "fetch_messages called with since: {:?}, limit: {:?}, offset: {:?}",
-        since, limit, offset
+        "fetch_messages called with server_id: {:?}, since: {:?}, limit: {:?}, offset: {:?}",
+        server_id, since, limit, offset
     );
 
-    match state.get_client() {
-        Ok(client) => {
+    match state.get_client(server_id.as_deref()) {
+        Ok((server_id, client)) => {
             debug!("Client found, fetching messages...");
             match client.get_messages(since, limit, offset).await {
-                Ok(messages) => {
-                    info!("Successfully fetched {} messages", messages.len());
-                    Ok(ApiResponse::success(messages))
+                Ok(mut page) => {
+                    info!("Successfully fetched {} messages", page.messages.len());
+                    for message in &mut page.messages {
+                        message.server_id = Some(server_id.clone());
+                    }
+                    state.cache_messages(&server_id, &page.messages);
+                    emit_unread_count(&app_handle);
+                    Ok(ApiResponse::success(page))
                 }
                 Err(e) => {
                     error!("Failed to fetch messages: {}", e);
@@ -461,20 +993,74 @@ async fn fetch_messages(
     }
 }
 
+// 断开指定服务器；未指定时断开所有服务器
 #[tauri::command]
-async fn disconnect_gotify(state: State<'_, AppState>) -> Result<ApiResponse<()>, String> {
-    state.clear_client();
+async fn disconnect_gotify(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    server_id: Option<String>,
+) -> Result<ApiResponse<()>, String> {
+    let disconnected = match server_id {
+        Some(id) => {
+            if state.connections.disconnect(&id) {
+                vec![id]
+            } else {
+                vec![]
+            }
+        }
+        None => state.connections.disconnect_all(),
+    };
+    for id in &disconnected {
+        emit_connection_status(&app_handle, id, &ConnectionState::Disconnected);
+    }
     Ok(ApiResponse::success(()))
 }
 
+#[tauri::command]
+async fn get_connection_status(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+) -> Result<ApiResponse<Vec<ServerStatus>>, String> {
+    let mut statuses: Vec<ServerStatus> = state
+        .connections
+        .statuses()
+        .into_iter()
+        .filter(|(id, _)| server_id.as_ref().map_or(true, |wanted| wanted == id))
+        .map(|(server_id, status)| ServerStatus { server_id, status })
+        .collect();
+    statuses.sort_by(|a, b| a.server_id.cmp(&b.server_id));
+    Ok(ApiResponse::success(statuses))
+}
+
+#[tauri::command]
+async fn get_stream_info(
+    state: State<'_, AppState>,
+) -> Result<ApiResponse<Vec<StreamInfo>>, String> {
+    Ok(ApiResponse::success(state.connections.stream_infos()))
+}
+
+// 网络恢复时由前端调用，跳过所有服务器当前的重连等待
+#[tauri::command]
+async fn reconnect_now(state: State<'_, AppState>) -> Result<ApiResponse<usize>, String> {
+    Ok(ApiResponse::success(state.connections.reconnect_now()))
+}
+
 #[tauri::command]
 async fn delete_message(
     state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    server_id: Option<String>,
     message_id: u64,
 ) -> Result<ApiResponse<()>, String> {
-    match state.get_client() {
-        Ok(client) => match client.delete_message(message_id).await {
-            Ok(_) => Ok(ApiResponse::success(())),
+    match state.get_client(server_id.as_deref()) {
+        Ok((server_id, client)) => match client.delete_message(message_id).await {
+            Ok(_) => {
+                if let Err(e) = state.store.delete_message(&server_id, message_id) {
+                    error!("Failed to delete cached message: {}", e);
+                }
+                emit_unread_count(&app_handle);
+                Ok(ApiResponse::success(()))
+            }
             Err(e) => Ok(ApiResponse::error(e.to_string())),
         },
         Err(e) => Ok(ApiResponse::error(e.to_string())),
@@ -482,26 +1068,49 @@ async fn delete_message(
 }
 
 #[tauri::command]
-async fn get_health(state: State<'_, AppState>) -> Result<ApiResponse<bool>, String> {
-    match state.get_client() {
-        Ok(client) => match client.get_health().await {
-            Ok(healthy) => Ok(ApiResponse::success(healthy)),
-            Err(e) => Ok(ApiResponse::error(e.to_string())),
-        },
+async fn fetch_application_messages(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    server_id: Option<String>,
+    app_id: u64,
+    since: Option<u64>,
+    limit: Option<u64>,
+) -> Result<ApiResponse<gotify::PagedMessages>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((server_id, client)) => {
+            match client.get_application_messages(app_id, since, limit).await {
+                Ok(mut page) => {
+                    for message in &mut page.messages {
+                        message.server_id = Some(server_id.clone());
+                    }
+                    state.cache_messages(&server_id, &page.messages);
+                    emit_unread_count(&app_handle);
+                    Ok(ApiResponse::success(page))
+                }
+                Err(e) => Ok(ApiResponse::error(e.to_string())),
+            }
+        }
         Err(e) => Ok(ApiResponse::error(e.to_string())),
     }
 }
 
+// 一次请求清空某个应用的全部消息，本地缓存同步删除
 #[tauri::command]
-async fn create_message(
+async fn delete_application_messages(
     state: State<'_, AppState>,
-    title: String,
-    message: String,
-    priority: i32,
-) -> Result<ApiResponse<gotify::Message>, String> {
-    match state.get_client() {
-        Ok(client) => match client.create_message(&title, &message, priority).await {
-            Ok(msg) => Ok(ApiResponse::success(msg)),
+    app_handle: tauri::AppHandle,
+    server_id: Option<String>,
+    app_id: u64,
+) -> Result<ApiResponse<()>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((server_id, client)) => match client.delete_application_messages(app_id).await {
+            Ok(_) => {
+                if let Err(e) = state.store.delete_messages(&server_id, Some(app_id)) {
+                    error!("Failed to delete cached messages: {}", e);
+                }
+                emit_unread_count(&app_handle);
+                Ok(ApiResponse::success(()))
+            }
             Err(e) => Ok(ApiResponse::error(e.to_string())),
         },
         Err(e) => Ok(ApiResponse::error(e.to_string())),
@@ -509,33 +1118,720 @@ async fn create_message(
 }
 
 #[tauri::command]
-async fn get_applications(
+async fn delete_all_messages(
     state: State<'_, AppState>,
-) -> Result<ApiResponse<Vec<gotify::Application>>, String> {
-    match state.get_client() {
-        Ok(client) => match client.get_applications().await {
-            Ok(apps) => Ok(ApiResponse::success(apps)),
+    app_handle: tauri::AppHandle,
+    server_id: Option<String>,
+) -> Result<ApiResponse<()>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((server_id, client)) => match client.delete_all_messages().await {
+            Ok(_) => {
+                if let Err(e) = state.store.delete_messages(&server_id, None) {
+                    error!("Failed to delete cached messages: {}", e);
+                }
+                emit_unread_count(&app_handle);
+                Ok(ApiResponse::success(()))
+            }
             Err(e) => Ok(ApiResponse::error(e.to_string())),
         },
         Err(e) => Ok(ApiResponse::error(e.to_string())),
     }
 }
 
-// 获取应用设置
+// 查询本地缓存的消息，离线时同样可用
 #[tauri::command]
-async fn get_app_settings(state: State<'_, AppState>) -> Result<ApiResponse<AppSettings>, String> {
-    Ok(ApiResponse::success(state.get_settings()))
+async fn query_local_messages(
+    state: State<'_, AppState>,
+    query: LocalMessageQuery,
+) -> Result<ApiResponse<Vec<StoredMessage>>, String> {
+    match state.store.query_messages(&query) {
+        Ok(messages) => Ok(ApiResponse::success(messages)),
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
 }
 
-// 更新应用设置
+// 批量修改消息的已读、星标、归档和稍后提醒状态
 #[tauri::command]
-async fn update_app_settings(
+async fn update_message_flags(
     state: State<'_, AppState>,
-    settings: AppSettings,
-) -> Result<ApiResponse<()>, String> {
-    state.set_settings(settings.clone());
-    save_settings_to_file(&settings)?;
-    Ok(ApiResponse::success(()))
+    app_handle: tauri::AppHandle,
+    server_id: String,
+    message_ids: Vec<u64>,
+    update: FlagUpdate,
+) -> Result<ApiResponse<usize>, String> {
+    match state.store.update_flags(&server_id, &message_ids, &update) {
+        Ok(changed) => {
+            emit_unread_count(&app_handle);
+            Ok(ApiResponse::success(changed))
+        }
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn mark_all_read(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    server_id: Option<String>,
+) -> Result<ApiResponse<usize>, String> {
+    match state.store.mark_all_read(server_id.as_deref()) {
+        Ok(changed) => {
+            emit_unread_count(&app_handle);
+            Ok(ApiResponse::success(changed))
+        }
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn get_unread_count(state: State<'_, AppState>) -> Result<ApiResponse<UnreadCount>, String> {
+    match state.store.unread_count() {
+        Ok(count) => Ok(ApiResponse::success(count)),
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+// 全文搜索本地缓存的消息
+#[tauri::command]
+async fn search_messages(
+    state: State<'_, AppState>,
+    query: SearchQuery,
+) -> Result<ApiResponse<Vec<StoredMessage>>, String> {
+    match state.store.search(&query) {
+        Ok(messages) => Ok(ApiResponse::success(messages)),
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn count_local_messages(
+    state: State<'_, AppState>,
+    query: LocalMessageQuery,
+) -> Result<ApiResponse<u64>, String> {
+    match state.store.count_messages(&query) {
+        Ok(count) => Ok(ApiResponse::success(count)),
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn get_server_version(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+) -> Result<ApiResponse<gotify::VersionInfo>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((server_id, client)) => match client.get_version().await {
+            Ok(version) => {
+                remember_server_version(&server_id, &version);
+                Ok(ApiResponse::success(version))
+            }
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn get_health(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+) -> Result<ApiResponse<bool>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.get_health().await {
+            Ok(healthy) => Ok(ApiResponse::success(healthy)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn create_message(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    req: CreateMessageRequest,
+) -> Result<ApiResponse<gotify::Message>, String> {
+    let (server_id, client) = match state.get_client(server_id.as_deref()) {
+        Ok(connection) => connection,
+        Err(e) => return Ok(ApiResponse::error(e.to_string())),
+    };
+    // 消息只能通过应用令牌创建，先查出所选应用的令牌
+    let apps = match client.get_applications().await {
+        Ok(apps) => apps,
+        Err(e) => return Ok(ApiResponse::error(e.to_string())),
+    };
+    state.cache_applications(&server_id, &apps);
+    let Some(app_token) = apps
+        .into_iter()
+        .find(|app| app.id == req.app_id)
+        .and_then(|app| app.token)
+    else {
+        return Ok(ApiResponse::error(format!(
+            "Application {} not found or has no token",
+            req.app_id
+        )));
+    };
+    match client.create_message(&app_token, &req.to_message()).await {
+        Ok(mut msg) => {
+            msg.server_id = Some(server_id);
+            Ok(ApiResponse::success(msg))
+        }
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn get_applications(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+) -> Result<ApiResponse<Vec<gotify::Application>>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((server_id, client)) => match client.get_applications().await {
+            Ok(apps) => {
+                state.cache_applications(&server_id, &apps);
+                Ok(ApiResponse::success(apps))
+            }
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn create_application(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    name: String,
+    description: String,
+    default_priority: i32,
+) -> Result<ApiResponse<gotify::Application>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((server_id, client)) => match client
+            .create_application(&name, &description, default_priority)
+            .await
+        {
+            Ok(app) => {
+                state.cache_applications(&server_id, std::slice::from_ref(&app));
+                Ok(ApiResponse::success(app))
+            }
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn update_application(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    app_id: u64,
+    name: String,
+    description: String,
+    default_priority: i32,
+) -> Result<ApiResponse<gotify::Application>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((server_id, client)) => match client
+            .update_application(app_id, &name, &description, default_priority)
+            .await
+        {
+            Ok(app) => {
+                state.cache_applications(&server_id, std::slice::from_ref(&app));
+                Ok(ApiResponse::success(app))
+            }
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn delete_application(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    app_id: u64,
+) -> Result<ApiResponse<()>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.delete_application(app_id).await {
+            Ok(_) => Ok(ApiResponse::success(())),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+// 上传应用图标，data 为前端读取的文件内容
+#[tauri::command]
+async fn upload_application_image(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    app_id: u64,
+    file_name: String,
+    data: Vec<u8>,
+) -> Result<ApiResponse<gotify::Application>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client
+            .upload_application_image(app_id, &file_name, data)
+            .await
+        {
+            Ok(app) => Ok(ApiResponse::success(app)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn delete_application_image(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    app_id: u64,
+) -> Result<ApiResponse<()>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.delete_application_image(app_id).await {
+            Ok(_) => Ok(ApiResponse::success(())),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn get_clients(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+) -> Result<ApiResponse<Vec<gotify::Client>>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.get_clients().await {
+            Ok(clients) => Ok(ApiResponse::success(clients)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn create_client(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    name: String,
+) -> Result<ApiResponse<gotify::Client>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.create_client(&name).await {
+            Ok(created) => Ok(ApiResponse::success(created)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn update_client(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    client_id: u64,
+    name: String,
+) -> Result<ApiResponse<gotify::Client>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.update_client(client_id, &name).await {
+            Ok(updated) => Ok(ApiResponse::success(updated)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn delete_client(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    client_id: u64,
+) -> Result<ApiResponse<()>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.delete_client(client_id).await {
+            Ok(_) => Ok(ApiResponse::success(())),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+// 更换本应用使用的客户端令牌：创建新令牌、切换连接并保存配置，最后吊销旧令牌
+async fn rotate_token(
+    app_handle: &tauri::AppHandle,
+    server_id: Option<&str>,
+) -> Result<gotify::Client, String> {
+    let state = app_handle.state::<AppState>();
+    let (server_id, client) = state.get_client(server_id)?;
+    let rotation = client.rotate_token().await.map_err(|e| e.to_string())?;
+
+    let mut configs = load_configs();
+    if let Some(config) = configs.iter_mut().find(|c| c.id == server_id) {
+        config.token = rotation.created.token.clone();
+        if let Err(e) = save_configs(&configs) {
+            revoke_unsaved_token(&rotation.client, rotation.created.id).await;
+            return Err(e);
+        }
+    }
+    start_connection(app_handle, &server_id, rotation.client.clone());
+
+    if let Some(old) = rotation.previous {
+        if let Err(e) = rotation.client.delete_client(old.id).await {
+            error!("Failed to revoke previous client token: {}", e);
+        }
+    }
+    info!("Rotated client token for {}", server_id);
+    Ok(rotation.created)
+}
+
+#[tauri::command]
+async fn rotate_client_token(
+    app_handle: tauri::AppHandle,
+    server_id: Option<String>,
+) -> Result<ApiResponse<gotify::Client>, String> {
+    Ok(ApiResponse::from_result(
+        rotate_token(&app_handle, server_id.as_deref()).await,
+    ))
+}
+
+#[tauri::command]
+async fn get_current_user(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+) -> Result<ApiResponse<gotify::User>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.get_current_user().await {
+            Ok(user) => Ok(ApiResponse::success(user)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn change_password(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    new_password: String,
+) -> Result<ApiResponse<()>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.change_password(&new_password).await {
+            Ok(_) => Ok(ApiResponse::success(())),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+// 以下用户管理命令需要管理员账号
+#[tauri::command]
+async fn get_users(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+) -> Result<ApiResponse<Vec<gotify::User>>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.get_users().await {
+            Ok(users) => Ok(ApiResponse::success(users)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn get_user(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    user_id: u64,
+) -> Result<ApiResponse<gotify::User>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.get_user(user_id).await {
+            Ok(user) => Ok(ApiResponse::success(user)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn create_user(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    name: String,
+    password: String,
+    admin: bool,
+) -> Result<ApiResponse<gotify::User>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.create_user(&name, &password, admin).await {
+            Ok(user) => Ok(ApiResponse::success(user)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn update_user(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    user_id: u64,
+    name: String,
+    password: Option<String>,
+    admin: bool,
+) -> Result<ApiResponse<gotify::User>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client
+            .update_user(user_id, &name, password.as_deref(), admin)
+            .await
+        {
+            Ok(user) => Ok(ApiResponse::success(user)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn delete_user(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    user_id: u64,
+) -> Result<ApiResponse<()>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.delete_user(user_id).await {
+            Ok(_) => Ok(ApiResponse::success(())),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn get_plugins(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+) -> Result<ApiResponse<Vec<gotify::Plugin>>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.get_plugins().await {
+            Ok(plugins) => Ok(ApiResponse::success(plugins)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn set_plugin_enabled(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    plugin_id: u64,
+    enabled: bool,
+) -> Result<ApiResponse<()>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.set_plugin_enabled(plugin_id, enabled).await {
+            Ok(_) => Ok(ApiResponse::success(())),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn get_plugin_config(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    plugin_id: u64,
+) -> Result<ApiResponse<String>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.get_plugin_config(plugin_id).await {
+            Ok(yaml) => Ok(ApiResponse::success(yaml)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn update_plugin_config(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    plugin_id: u64,
+    config: String,
+) -> Result<ApiResponse<()>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.update_plugin_config(plugin_id, &config).await {
+            Ok(_) => Ok(ApiResponse::success(())),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+#[tauri::command]
+async fn get_plugin_display(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+    plugin_id: u64,
+) -> Result<ApiResponse<String>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => match client.get_plugin_display(plugin_id).await {
+            Ok(display) => Ok(ApiResponse::success(display)),
+            Err(e) => Ok(ApiResponse::error(e.to_string())),
+        },
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
+}
+
+// 获取应用设置
+#[tauri::command]
+async fn get_app_settings(state: State<'_, AppState>) -> Result<ApiResponse<AppSettings>, String> {
+    Ok(ApiResponse::success(state.get_settings()))
+}
+
+// 更新应用设置
+#[tauri::command]
+async fn update_app_settings(
+    state: State<'_, AppState>,
+    settings: AppSettings,
+) -> Result<ApiResponse<()>, String> {
+    // 令牌存储方式由 configure_vault 单独修改
+    let mut settings = settings;
+    settings.vault = state.get_settings().vault;
+    state.set_settings(settings.clone());
+    save_settings_to_file(&settings)?;
+    Ok(ApiResponse::success(()))
+}
+
+#[derive(serde::Serialize)]
+struct VaultStatus {
+    #[serde(flatten)]
+    settings: VaultSettings,
+    locked: bool,
+    secret_service_available: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct ConfigureVaultRequest {
+    mode: VaultMode,
+    #[serde(default)]
+    key_file: Option<String>,
+    // 仅用于密码模式，不会保存
+    #[serde(default)]
+    passphrase: Option<String>,
+}
+
+// 令牌保险库的状态
+#[tauri::command]
+async fn get_vault_status(state: State<'_, AppState>) -> Result<ApiResponse<VaultStatus>, String> {
+    let settings = state.get_settings().vault;
+    Ok(ApiResponse::success(VaultStatus {
+        locked: settings.mode != VaultMode::Plaintext && vault::unlocked().is_none(),
+        secret_service_available: vault::secret_service::is_available(),
+        settings,
+    }))
+}
+
+// 切换令牌的保存方式，使用新的密钥重新加密所有令牌
+#[tauri::command]
+async fn configure_vault(
+    state: State<'_, AppState>,
+    req: ConfigureVaultRequest,
+) -> Result<ApiResponse<()>, String> {
+    if let Some(passphrase) = &req.passphrase {
+        logging::register_secret(passphrase);
+    }
+    let mut config = load_app_config();
+    let previous = config.settings.vault.clone();
+    let unlocked = vault::unlocked();
+    if previous.mode != VaultMode::Plaintext && unlocked.is_none() {
+        return Ok(ApiResponse::error(VaultError::Locked.to_string()));
+    }
+
+    let settings = VaultSettings {
+        mode: req.mode,
+        key_file: req.key_file.filter(|p| !p.trim().is_empty()),
+    };
+    // 密钥环中只有一个密钥条目，新密钥会覆盖它；先记下旧密钥，保存失败时恢复
+    let previous_key = if previous.mode == VaultMode::SecretService {
+        match vault::secret_service::lookup() {
+            Ok(key) => Some(key),
+            Err(e) => return Ok(ApiResponse::error(e.to_string())),
+        }
+    } else {
+        None
+    };
+    let created = match vault::create(&settings, req.passphrase.as_deref(), get_vault_path()) {
+        Ok(created) => created,
+        Err(e) => {
+            if settings.mode == VaultMode::SecretService {
+                restore_vault_key(previous_key.as_deref());
+            }
+            return Ok(ApiResponse::error(e.to_string()));
+        }
+    };
+    config.settings.vault = settings.clone();
+    vault::set_unlocked(created);
+    if let Err(e) = save_app_config(&config) {
+        restore_vault(&mut config, previous, previous_key, unlocked);
+        return Ok(ApiResponse::error(e));
+    }
+
+    if settings.mode == VaultMode::Plaintext {
+        if let Err(e) = std::fs::remove_file(get_vault_path()) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                error!("Failed to remove token vault: {}", e);
+            }
+        }
+    }
+    if previous.mode == VaultMode::SecretService && settings.mode != VaultMode::SecretService {
+        if let Err(e) = vault::secret_service::clear() {
+            error!("Failed to remove vault key from the keyring: {}", e);
+        }
+    }
+
+    let mut app_settings = state.get_settings();
+    app_settings.vault = settings;
+    state.set_settings(app_settings);
+    info!("Token storage set to {:?}", req.mode);
+    Ok(ApiResponse::success(()))
+}
+
+// 切换令牌保存方式失败时回到原来的保险库：恢复密钥环中的旧密钥，并用旧密钥
+// 重新写入保险库文件（配置写入失败前保险库文件可能已用新密钥加密）
+fn restore_vault(
+    config: &mut AppConfig,
+    previous: VaultSettings,
+    previous_key: Option<String>,
+    unlocked: Option<vault::Vault>,
+) {
+    if config.settings.vault.mode == VaultMode::SecretService {
+        restore_vault_key(previous_key.as_deref());
+    }
+    vault::set_unlocked(unlocked);
+    config.settings.vault = previous;
+    if let Err(e) = seal_tokens(config) {
+        error!("Failed to restore token vault: {}", e);
+    }
+}
+
+// 写回原来的密钥环密钥；原来没有使用密钥环时删除新写入的密钥
+fn restore_vault_key(previous_key: Option<&str>) {
+    let restored = match previous_key {
+        Some(key) => vault::secret_service::store(key),
+        None => vault::secret_service::clear(),
+    };
+    if let Err(e) = restored {
+        error!("Failed to restore vault key in the keyring: {}", e);
+    }
+}
+
+// 使用密码解锁保险库，解锁状态保持到应用退出
+#[tauri::command]
+async fn unlock_vault(passphrase: String) -> Result<ApiResponse<()>, String> {
+    logging::register_secret(&passphrase);
+    match vault::unlock(&passphrase, get_vault_path()) {
+        Ok(()) => Ok(ApiResponse::success(())),
+        Err(e) => Ok(ApiResponse::error(e.to_string())),
+    }
 }
 
 // 切换开机启动
@@ -606,17 +1902,13 @@ async fn hide_window(app: tauri::AppHandle) -> Result<ApiResponse<()>, String> {
 
 // 测试 WebSocket 连接
 #[tauri::command]
-async fn test_websocket(state: State<'_, AppState>) -> Result<ApiResponse<String>, String> {
-    match state.get_client() {
-        Ok(client) => {
-            let ws_url = format!(
-                "{}/stream?token={}",
-                client
-                    .get_base_url()
-                    .replace("http://", "ws://")
-                    .replace("https://", "wss://"),
-                client.get_token()
-            );
+async fn test_websocket(
+    state: State<'_, AppState>,
+    server_id: Option<String>,
+) -> Result<ApiResponse<String>, String> {
+    match state.get_client(server_id.as_deref()) {
+        Ok((_, client)) => {
+            let ws_url = client.stream_url();
             info!("Test WebSocket URL: {}", ws_url);
             Ok(ApiResponse::success(ws_url))
         }
@@ -635,6 +1927,7 @@ async fn test_emit_event(app: tauri::AppHandle) -> Result<ApiResponse<String>, S
         timestamp: chrono::Utc::now().to_rfc3339(),
         app_id: 1,
         extras: None,
+        server_id: None,
     };
 
     match app.emit("new-message", &test_message) {
@@ -747,13 +2040,19 @@ fn main() {
         _ => log::LevelFilter::Info, // 默认 info
     };
 
-    env_logger::Builder::from_default_env()
-        .filter_level(level_filter)
-        .filter_module("chromium", log::LevelFilter::Error) // 抑制 Chromium 的非错误日志
-        .filter_module("ui::gfx", log::LevelFilter::Error) // 抑制 UI/GFX 的 INFO 日志
-        .init();
+    // 所有日志都会经过脱敏处理，避免令牌写入日志文件
+    logging::init(level_filter);
 
     info!("Loaded settings: {:?}", settings);
+
+    // 旧版本写入的配置文件可能对其他用户可读
+    if let Err(e) = vault::restrict_permissions(&get_config_path()) {
+        error!("Failed to restrict config file permissions: {}", e);
+    }
+    // 密钥文件和系统密钥环无需用户输入，启动时直接解锁
+    if let Err(e) = vault::unlock_without_prompt(&settings.vault, get_vault_path()) {
+        error!("Failed to unlock token vault: {}", e);
+    }
     info!("Log level set to: {:?}", level_filter);
 
     tauri::Builder::default()
@@ -818,12 +2117,50 @@ fn main() {
         })
         .invoke_handler(tauri::generate_handler![
             connect_to_gotify,
+            connect_all_servers,
+            login_to_gotify,
+            set_server_enabled,
             fetch_messages,
             disconnect_gotify,
+            get_stream_info,
+            get_connection_status,
+            reconnect_now,
             delete_message,
+            fetch_application_messages,
+            delete_application_messages,
+            delete_all_messages,
+            query_local_messages,
+            count_local_messages,
+            search_messages,
+            update_message_flags,
+            mark_all_read,
+            get_unread_count,
             get_health,
+            get_server_version,
             create_message,
             get_applications,
+            create_application,
+            update_application,
+            delete_application,
+            upload_application_image,
+            delete_application_image,
+            get_clients,
+            create_client,
+            update_client,
+            delete_client,
+            rotate_client_token,
+            get_current_user,
+            change_password,
+            get_users,
+            get_user,
+            create_user,
+            update_user,
+            delete_user,
+            get_plugins,
+            set_plugin_enabled,
+            get_plugin_config,
+            update_plugin_config,
+            get_plugin_display,
             save_config,
             get_configs,
             delete_config,
@@ -832,6 +2169,9 @@ fn main() {
             update_config,
             get_app_settings,
             update_app_settings,
+            get_vault_status,
+            configure_vault,
+            unlock_vault,
             toggle_autostart,
             show_window,
             hide_window,
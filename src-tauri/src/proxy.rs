@@ -0,0 +1,475 @@
+use crate::gotify::GotifyError;
+use base64::Engine;
+use std::net::IpAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use url::Url;
+
+// 代理响应头的最大长度，防止异常代理无限输出
+const MAX_CONNECT_RESPONSE: usize = 8 * 1024;
+
+/// Where the proxy of a connection comes from.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyMode {
+    // 读取 HTTPS_PROXY / HTTP_PROXY / ALL_PROXY / NO_PROXY 环境变量
+    #[default]
+    System,
+    // 直连，忽略环境变量
+    Direct,
+    // 使用配置中的代理地址
+    Manual,
+}
+
+/// Proxy settings, either global (`AppSettings.proxy`) or per server.
+/// `Debug` masks the password so settings can be logged.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default, PartialEq)]
+pub struct ProxyOptions {
+    #[serde(default)]
+    pub mode: ProxyMode,
+    // http://host:port、socks5://host:port 或 socks5h://host:port（由代理解析域名）
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    // 逗号分隔的直连主机，如 "localhost,.internal,10.0.0.1"
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProxyKind {
+    /// HTTP proxy tunnelling with `CONNECT`.
+    Http,
+    /// SOCKS5; with `remote_dns` the proxy resolves the target host.
+    Socks5 { remote_dns: bool },
+}
+
+/// The proxy a connection to one target goes through.
+#[derive(Clone, PartialEq)]
+pub struct Proxy {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    pub auth: Option<(String, String)>,
+}
+
+// 密码只显示是否设置，URL 中的用户信息一并隐藏
+const MASK: &str = "***";
+
+fn masked_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) if !parsed.username().is_empty() || parsed.password().is_some() => {
+            let _ = parsed.set_username(MASK);
+            let _ = parsed.set_password(None);
+            parsed.into()
+        }
+        _ => url.to_string(),
+    }
+}
+
+impl std::fmt::Debug for ProxyOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProxyOptions")
+            .field("mode", &self.mode)
+            .field("url", &self.url.as_deref().map(masked_url))
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| MASK))
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Proxy")
+            .field("kind", &self.kind)
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field(
+                "auth",
+                &self.auth.as_ref().map(|(username, _)| (username, MASK)),
+            )
+            .finish()
+    }
+}
+
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+// 没有写协议时按 HTTP 代理处理
+fn proxy_url(url: &str) -> Result<Url, GotifyError> {
+    let url = if url.contains("://") {
+        url.to_string()
+    } else {
+        format!("http://{}", url)
+    };
+    Url::parse(&url).map_err(|e| GotifyError::ProxyError(format!("invalid proxy URL: {}", e)))
+}
+
+impl ProxyOptions {
+    /// Configures the REST client. In `System` mode reqwest already honors
+    /// the proxy environment variables.
+    pub fn apply(
+        &self,
+        builder: reqwest::ClientBuilder,
+    ) -> Result<reqwest::ClientBuilder, GotifyError> {
+        match self.mode {
+            ProxyMode::System => Ok(builder),
+            ProxyMode::Direct => Ok(builder.no_proxy()),
+            ProxyMode::Manual => {
+                let url = self.manual_url()?;
+                let proxy = reqwest::Proxy::all(url.as_str())
+                    .map_err(|e| GotifyError::ProxyError(e.to_string()))?
+                    .no_proxy(
+                        non_empty(self.no_proxy.as_deref()).and_then(reqwest::NoProxy::from_string),
+                    );
+                Ok(builder.proxy(proxy))
+            }
+        }
+    }
+
+    // 配置中的用户名和密码优先于地址中的认证信息
+    fn manual_url(&self) -> Result<Url, GotifyError> {
+        let Some(url) = non_empty(self.url.as_deref()) else {
+            return Err(GotifyError::ProxyError("proxy URL is not set".to_string()));
+        };
+        let mut url = proxy_url(url)?;
+        if let Some(username) = non_empty(self.username.as_deref()) {
+            if url.set_username(username).is_err()
+                || url.set_password(self.password.as_deref()).is_err()
+            {
+                return Err(GotifyError::ProxyError(
+                    "proxy URL cannot carry credentials".to_string(),
+                ));
+            }
+        }
+        Ok(url)
+    }
+
+    /// Resolves the proxy for a connection to `target`, if any.
+    pub fn resolve(&self, target: &Url) -> Result<Option<Proxy>, GotifyError> {
+        self.resolve_with(target, |name| std::env::var(name).ok())
+    }
+
+    /// [`resolve`](Self::resolve) reading environment variables through `env`.
+    pub fn resolve_with(
+        &self,
+        target: &Url,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Option<Proxy>, GotifyError> {
+        let host = target
+            .host_str()
+            .map(|h| h.trim_start_matches('[').trim_end_matches(']'))
+            .unwrap_or_default();
+        match self.mode {
+            ProxyMode::Direct => Ok(None),
+            ProxyMode::Manual => {
+                if non_empty(self.no_proxy.as_deref()).is_some_and(|list| bypass(list, host)) {
+                    return Ok(None);
+                }
+                parse_proxy(&self.manual_url()?).map(Some)
+            }
+            ProxyMode::System => {
+                let var = |name: &str| {
+                    env(name)
+                        .or_else(|| env(&name.to_lowercase()))
+                        .filter(|v| !v.trim().is_empty())
+                };
+                if var("NO_PROXY").is_some_and(|list| bypass(&list, host)) {
+                    return Ok(None);
+                }
+                let secure = matches!(target.scheme(), "https" | "wss");
+                let url = if secure {
+                    var("HTTPS_PROXY")
+                } else {
+                    var("HTTP_PROXY")
+                };
+                match url.or_else(|| var("ALL_PROXY")) {
+                    Some(url) => parse_proxy(&proxy_url(url.trim())?).map(Some),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+}
+
+fn parse_proxy(url: &Url) -> Result<Proxy, GotifyError> {
+    let kind = match url.scheme() {
+        "http" => ProxyKind::Http,
+        "socks5" => ProxyKind::Socks5 { remote_dns: false },
+        "socks5h" => ProxyKind::Socks5 { remote_dns: true },
+        scheme => {
+            return Err(GotifyError::ProxyError(format!(
+                "unsupported proxy scheme: {}",
+                scheme
+            )))
+        }
+    };
+    let host = url
+        .host_str()
+        .map(|h| h.trim_start_matches('[').trim_end_matches(']').to_string())
+        .ok_or_else(|| GotifyError::ProxyError(format!("proxy URL has no host: {}", url)))?;
+    let port = url.port().unwrap_or(match kind {
+        ProxyKind::Http => 8080,
+        ProxyKind::Socks5 { .. } => 1080,
+    });
+    let auth = (!url.username().is_empty()).then(|| {
+        (
+            percent_decode(url.username()),
+            percent_decode(url.password().unwrap_or_default()),
+        )
+    });
+    Ok(Proxy {
+        kind,
+        host,
+        port,
+        auth,
+    })
+}
+
+// URL 中的认证信息是百分号编码的
+fn percent_decode(value: &str) -> String {
+    url::form_urlencoded::parse(format!("v={}", value.replace('+', "%2B")).as_bytes())
+        .next()
+        .map(|(_, v)| v.into_owned())
+        .unwrap_or_default()
+}
+
+/// Whether `host` matches a `NO_PROXY` style list: `*`, exact hosts or IPs,
+/// and domain suffixes (`example.com` and `.example.com` both cover
+/// `api.example.com`).
+pub fn bypass(no_proxy: &str, host: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    no_proxy
+        .split(',')
+        .map(|entry| entry.trim().to_ascii_lowercase())
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+            let domain = entry.trim_start_matches('.');
+            host == domain || host.ends_with(&format!(".{}", domain))
+        })
+}
+
+fn proxy_error(err: std::io::Error) -> GotifyError {
+    GotifyError::ProxyError(err.to_string())
+}
+
+fn protocol_error(message: impl Into<String>) -> GotifyError {
+    GotifyError::ProxyError(message.into())
+}
+
+/// Opens a TCP connection to `host:port`, tunnelled through `proxy` if set.
+pub async fn connect(
+    host: &str,
+    port: u16,
+    proxy: Option<&Proxy>,
+) -> Result<TcpStream, GotifyError> {
+    let Some(proxy) = proxy else {
+        return TcpStream::connect((host, port))
+            .await
+            .map_err(|e| GotifyError::StreamError(e.to_string()));
+    };
+    let mut stream = TcpStream::connect((proxy.host.as_str(), proxy.port))
+        .await
+        .map_err(|e| {
+            GotifyError::ProxyError(format!(
+                "cannot reach proxy {}:{}: {}",
+                proxy.host, proxy.port, e
+            ))
+        })?;
+    match proxy.kind {
+        ProxyKind::Http => http_connect(&mut stream, host, port, proxy.auth.as_ref()).await?,
+        ProxyKind::Socks5 { remote_dns } => {
+            socks5_connect(&mut stream, host, port, remote_dns, proxy.auth.as_ref()).await?
+        }
+    }
+    Ok(stream)
+}
+
+async fn http_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    auth: Option<&(String, String)>,
+) -> Result<(), GotifyError> {
+    let authority = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+    if let Some((username, password)) = auth {
+        let credentials =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", credentials));
+    }
+    request.push_str("\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(proxy_error)?;
+
+    // 逐字节读取响应头，避免读走隧道建立后的数据
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_CONNECT_RESPONSE {
+            return Err(protocol_error("proxy response header is too long"));
+        }
+        let byte = stream.read_u8().await.map_err(proxy_error)?;
+        response.push(byte);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+    match status {
+        "200" => Ok(()),
+        "407" => Err(protocol_error(format!(
+            "proxy authentication required: {}",
+            status_line
+        ))),
+        _ => Err(protocol_error(format!(
+            "proxy refused CONNECT: {}",
+            status_line
+        ))),
+    }
+}
+
+const SOCKS_VERSION: u8 = 5;
+const SOCKS_NO_AUTH: u8 = 0;
+const SOCKS_USER_PASS: u8 = 2;
+const SOCKS_NO_METHOD: u8 = 0xff;
+
+async fn socks5_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    remote_dns: bool,
+    auth: Option<&(String, String)>,
+) -> Result<(), GotifyError> {
+    let methods: &[u8] = if auth.is_some() {
+        &[SOCKS_NO_AUTH, SOCKS_USER_PASS]
+    } else {
+        &[SOCKS_NO_AUTH]
+    };
+    let mut greeting = vec![SOCKS_VERSION, methods.len() as u8];
+    greeting.extend_from_slice(methods);
+    stream.write_all(&greeting).await.map_err(proxy_error)?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await.map_err(proxy_error)?;
+    if reply[0] != SOCKS_VERSION {
+        return Err(protocol_error("not a SOCKS5 proxy"));
+    }
+    match (reply[1], auth) {
+        (SOCKS_NO_AUTH, _) => {}
+        (SOCKS_USER_PASS, Some((username, password))) => {
+            // RFC 1929 用户名密码认证
+            if username.len() > 255 || password.len() > 255 {
+                return Err(protocol_error("SOCKS5 credentials are too long"));
+            }
+            let mut request = vec![1, username.len() as u8];
+            request.extend_from_slice(username.as_bytes());
+            request.push(password.len() as u8);
+            request.extend_from_slice(password.as_bytes());
+            stream.write_all(&request).await.map_err(proxy_error)?;
+            let mut status = [0u8; 2];
+            stream.read_exact(&mut status).await.map_err(proxy_error)?;
+            if status[1] != 0 {
+                return Err(protocol_error("SOCKS5 proxy rejected the credentials"));
+            }
+        }
+        (SOCKS_NO_METHOD, _) | (SOCKS_USER_PASS, None) => {
+            return Err(protocol_error(
+                "SOCKS5 proxy requires an authentication method we did not offer",
+            ))
+        }
+        (method, _) => {
+            return Err(protocol_error(format!(
+                "SOCKS5 proxy chose unsupported method {}",
+                method
+            )))
+        }
+    }
+
+    let mut request = vec![SOCKS_VERSION, 1, 0];
+    let address = match host.parse::<IpAddr>() {
+        Ok(ip) => Some(ip),
+        // socks5:// 在本地解析域名，socks5h:// 交给代理解析
+        Err(_) if !remote_dns => Some(
+            tokio::net::lookup_host((host, port))
+                .await
+                .map_err(proxy_error)?
+                .next()
+                .ok_or_else(|| protocol_error(format!("cannot resolve {}", host)))?
+                .ip(),
+        ),
+        Err(_) => None,
+    };
+    match address {
+        Some(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Some(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        None => {
+            if host.len() > 255 {
+                return Err(protocol_error("host name is too long for SOCKS5"));
+            }
+            request.push(3);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await.map_err(proxy_error)?;
+
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await.map_err(proxy_error)?;
+    if header[1] != 0 {
+        return Err(protocol_error(format!(
+            "SOCKS5 proxy could not connect to {}:{} ({})",
+            host,
+            port,
+            socks_reply(header[1])
+        )));
+    }
+    // 读掉代理返回的绑定地址和端口
+    let address_len = match header[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await.map_err(proxy_error)? as usize,
+        other => {
+            return Err(protocol_error(format!(
+                "SOCKS5 proxy returned unknown address type {}",
+                other
+            )))
+        }
+    };
+    let mut bound = vec![0u8; address_len + 2];
+    stream.read_exact(&mut bound).await.map_err(proxy_error)?;
+    Ok(())
+}
+
+fn socks_reply(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
@@ -0,0 +1,690 @@
+use crate::gotify::{Application, Message};
+use log::{error, info};
+use rusqlite::{params, params_from_iter, Connection, Row};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+pub type StoreResult<T> = Result<T, rusqlite::Error>;
+
+// 新建数据库直接使用的最终 schema，修改时需同步追加 MIGRATIONS
+const SCHEMA: &str = "
+    -- 使用显式的整数主键，保证全文索引的 rowid 对应关系在 VACUUM 后不变
+    CREATE TABLE messages (
+        key INTEGER PRIMARY KEY,
+        server_id TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        app_id INTEGER NOT NULL,
+        title TEXT,
+        message TEXT NOT NULL,
+        priority INTEGER NOT NULL,
+        timestamp TEXT NOT NULL,
+        created_at INTEGER,
+        extras TEXT,
+        read INTEGER NOT NULL DEFAULT 0,
+        starred INTEGER NOT NULL DEFAULT 0,
+        archived INTEGER NOT NULL DEFAULT 0,
+        snoozed_until INTEGER,
+        UNIQUE (server_id, id)
+    );
+    CREATE INDEX idx_messages_app ON messages (server_id, app_id, id);
+    CREATE INDEX idx_messages_created ON messages (created_at);
+    CREATE INDEX idx_messages_unread ON messages (server_id, read, archived);
+
+    CREATE TABLE applications (
+        server_id TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        PRIMARY KEY (server_id, id)
+    );
+
+    -- trigram 分词支持中文等无空格文本的子串搜索
+    CREATE VIRTUAL TABLE messages_fts USING fts5(
+        title, message, app_name, extras, tokenize = 'trigram'
+    );
+
+    CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages BEGIN
+        INSERT INTO messages_fts (rowid, title, message, app_name, extras)
+        VALUES (
+            new.key, new.title, new.message,
+            (SELECT name FROM applications WHERE server_id = new.server_id AND id = new.app_id),
+            new.extras
+        );
+    END;
+    CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+        DELETE FROM messages_fts WHERE rowid = old.key;
+    END;
+    -- 只在可搜索的列变化时重建索引，修改已读等状态不会重写全文索引
+    CREATE TRIGGER messages_fts_update AFTER UPDATE OF title, message, extras, app_id ON messages BEGIN
+        DELETE FROM messages_fts WHERE rowid = old.key;
+        INSERT INTO messages_fts (rowid, title, message, app_name, extras)
+        VALUES (
+            new.key, new.title, new.message,
+            (SELECT name FROM applications WHERE server_id = new.server_id AND id = new.app_id),
+            new.extras
+        );
+    END;
+    CREATE TRIGGER applications_fts_insert AFTER INSERT ON applications BEGIN
+        UPDATE messages_fts SET app_name = new.name
+        WHERE rowid IN (SELECT key FROM messages WHERE server_id = new.server_id AND app_id = new.id);
+    END;
+    CREATE TRIGGER applications_fts_update AFTER UPDATE ON applications BEGIN
+        UPDATE messages_fts SET app_name = new.name
+        WHERE rowid IN (SELECT key FROM messages WHERE server_id = new.server_id AND app_id = new.id);
+    END;
+";
+
+// 升级旧数据库用，每个元素对应一个 schema 版本，已执行的版本记录在 user_version 中
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE messages (
+        server_id TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        app_id INTEGER NOT NULL,
+        title TEXT,
+        message TEXT NOT NULL,
+        priority INTEGER NOT NULL,
+        timestamp TEXT NOT NULL,
+        extras TEXT,
+        PRIMARY KEY (server_id, id)
+    );
+    CREATE INDEX idx_messages_app ON messages (server_id, app_id, id);
+", "
+    -- 使用显式的整数主键，保证全文索引的 rowid 对应关系在 VACUUM 后不变
+    CREATE TABLE messages_v2 (
+        key INTEGER PRIMARY KEY,
+        server_id TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        app_id INTEGER NOT NULL,
+        title TEXT,
+        message TEXT NOT NULL,
+        priority INTEGER NOT NULL,
+        timestamp TEXT NOT NULL,
+        created_at INTEGER,
+        extras TEXT,
+        UNIQUE (server_id, id)
+    );
+    INSERT INTO messages_v2 (server_id, id, app_id, title, message, priority, timestamp, created_at, extras)
+        SELECT server_id, id, app_id, title, message, priority, timestamp, unixepoch(timestamp), extras
+        FROM messages;
+    DROP TABLE messages;
+    ALTER TABLE messages_v2 RENAME TO messages;
+    CREATE INDEX idx_messages_app ON messages (server_id, app_id, id);
+    CREATE INDEX idx_messages_created ON messages (created_at);
+
+    CREATE TABLE applications (
+        server_id TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        PRIMARY KEY (server_id, id)
+    );
+
+    -- trigram 分词支持中文等无空格文本的子串搜索
+    CREATE VIRTUAL TABLE messages_fts USING fts5(
+        title, message, app_name, extras, tokenize = 'trigram'
+    );
+    INSERT INTO messages_fts (rowid, title, message, app_name, extras)
+        SELECT key, title, message, NULL, extras FROM messages;
+
+    CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages BEGIN
+        INSERT INTO messages_fts (rowid, title, message, app_name, extras)
+        VALUES (
+            new.key, new.title, new.message,
+            (SELECT name FROM applications WHERE server_id = new.server_id AND id = new.app_id),
+            new.extras
+        );
+    END;
+    CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+        DELETE FROM messages_fts WHERE rowid = old.key;
+    END;
+    CREATE TRIGGER messages_fts_update AFTER UPDATE ON messages BEGIN
+        DELETE FROM messages_fts WHERE rowid = old.key;
+        INSERT INTO messages_fts (rowid, title, message, app_name, extras)
+        VALUES (
+            new.key, new.title, new.message,
+            (SELECT name FROM applications WHERE server_id = new.server_id AND id = new.app_id),
+            new.extras
+        );
+    END;
+    CREATE TRIGGER applications_fts_insert AFTER INSERT ON applications BEGIN
+        UPDATE messages_fts SET app_name = new.name
+        WHERE rowid IN (SELECT key FROM messages WHERE server_id = new.server_id AND app_id = new.id);
+    END;
+    CREATE TRIGGER applications_fts_update AFTER UPDATE ON applications BEGIN
+        UPDATE messages_fts SET app_name = new.name
+        WHERE rowid IN (SELECT key FROM messages WHERE server_id = new.server_id AND app_id = new.id);
+    END;
+", "
+    ALTER TABLE messages ADD COLUMN read INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE messages ADD COLUMN starred INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE messages ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE messages ADD COLUMN snoozed_until INTEGER;
+    -- 升级前缓存的消息都已展示过，视为已读
+    UPDATE messages SET read = 1;
+    CREATE INDEX idx_messages_unread ON messages (server_id, read, archived);
+", "
+    -- 只在可搜索的列变化时重建索引，修改已读等状态不再重写全文索引
+    DROP TRIGGER messages_fts_update;
+    CREATE TRIGGER messages_fts_update AFTER UPDATE OF title, message, extras, app_id ON messages BEGIN
+        DELETE FROM messages_fts WHERE rowid = old.key;
+        INSERT INTO messages_fts (rowid, title, message, app_name, extras)
+        VALUES (
+            new.key, new.title, new.message,
+            (SELECT name FROM applications WHERE server_id = new.server_id AND id = new.app_id),
+            new.extras
+        );
+    END;
+"];
+
+// 查询消息时统一选取的列，顺序与 stored_message_from_row 对应
+const MESSAGE_COLUMNS: &str = "m.server_id, m.id, m.app_id, m.title, m.message, m.priority, \
+    m.timestamp, m.extras, m.read, m.starred, m.archived, m.snoozed_until";
+
+// 未读：未读、未归档且不在稍后提醒期间
+const UNREAD_CONDITION: &str = "m.read = 0 AND m.archived = 0 \
+    AND (m.snoozed_until IS NULL OR m.snoozed_until <= unixepoch('now'))";
+
+// trigram 分词器只能匹配至少 3 个字符的片段，更短的词退回到 LIKE
+const MIN_FTS_TERM_CHARS: usize = 3;
+
+/// Filters and paging for locally cached messages. Results are ordered
+/// newest first, like the server's `/message` endpoint.
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct LocalMessageQuery {
+    pub server_id: Option<String>,
+    pub app_id: Option<u64>,
+    pub min_priority: Option<i32>,
+    // 只返回 id 小于该值的消息，用于向前翻页
+    pub before_id: Option<u64>,
+    pub read: Option<bool>,
+    pub starred: Option<bool>,
+    pub archived: Option<bool>,
+    // 为 true 时不返回仍在稍后提醒期间的消息
+    pub hide_snoozed: bool,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+impl LocalMessageQuery {
+    fn where_clause(&self) -> (String, Vec<rusqlite::types::Value>) {
+        use rusqlite::types::Value;
+
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(server_id) = &self.server_id {
+            conditions.push("server_id = ?");
+            values.push(Value::Text(server_id.clone()));
+        }
+        if let Some(app_id) = self.app_id {
+            conditions.push("app_id = ?");
+            values.push(Value::Integer(app_id as i64));
+        }
+        if let Some(min_priority) = self.min_priority {
+            conditions.push("priority >= ?");
+            values.push(Value::Integer(min_priority as i64));
+        }
+        if let Some(before_id) = self.before_id {
+            conditions.push("id < ?");
+            values.push(Value::Integer(before_id as i64));
+        }
+        for (column, flag) in [
+            ("read = ?", self.read),
+            ("starred = ?", self.starred),
+            ("archived = ?", self.archived),
+        ] {
+            if let Some(flag) = flag {
+                conditions.push(column);
+                values.push(Value::Integer(flag as i64));
+            }
+        }
+        if self.hide_snoozed {
+            conditions.push("(snoozed_until IS NULL OR snoozed_until <= unixepoch('now'))");
+        }
+
+        if conditions.is_empty() {
+            (String::new(), values)
+        } else {
+            (format!("WHERE {}", conditions.join(" AND ")), values)
+        }
+    }
+}
+
+/// Full-text search over cached messages. `text` is split on whitespace and
+/// every term must appear in the title, body, application name or extras.
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SearchQuery {
+    pub text: String,
+    pub server_id: Option<String>,
+    pub app_id: Option<u64>,
+    pub min_priority: Option<i32>,
+    pub max_priority: Option<i32>,
+    // RFC 3339 时间，包含边界
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+impl SearchQuery {
+    fn where_clause(&self) -> (Vec<String>, Vec<rusqlite::types::Value>) {
+        use rusqlite::types::Value;
+
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+
+        let (fts_terms, short_terms): (Vec<&str>, Vec<&str>) = self
+            .text
+            .split_whitespace()
+            .partition(|term| term.chars().count() >= MIN_FTS_TERM_CHARS);
+        if !fts_terms.is_empty() {
+            // 每个词都作为短语加引号，避免用户输入被解析为 FTS 语法
+            let expr = fts_terms
+                .iter()
+                .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            conditions.push("messages_fts MATCH ?".to_string());
+            values.push(Value::Text(expr));
+        }
+        for term in short_terms {
+            let columns = ["f.title", "f.message", "f.app_name", "f.extras"];
+            let any_column = columns
+                .iter()
+                .map(|column| format!("{} LIKE ? ESCAPE '\\'", column))
+                .collect::<Vec<_>>()
+                .join(" OR ");
+            conditions.push(format!("({})", any_column));
+            let pattern = format!("%{}%", escape_like(term));
+            values.extend(columns.iter().map(|_| Value::Text(pattern.clone())));
+        }
+
+        if let Some(server_id) = &self.server_id {
+            conditions.push("m.server_id = ?".to_string());
+            values.push(Value::Text(server_id.clone()));
+        }
+        if let Some(app_id) = self.app_id {
+            conditions.push("m.app_id = ?".to_string());
+            values.push(Value::Integer(app_id as i64));
+        }
+        if let Some(min_priority) = self.min_priority {
+            conditions.push("m.priority >= ?".to_string());
+            values.push(Value::Integer(min_priority as i64));
+        }
+        if let Some(max_priority) = self.max_priority {
+            conditions.push("m.priority <= ?".to_string());
+            values.push(Value::Integer(max_priority as i64));
+        }
+        if let Some(since) = &self.since {
+            conditions.push("m.created_at >= unixepoch(?)".to_string());
+            values.push(Value::Text(since.clone()));
+        }
+        if let Some(until) = &self.until {
+            conditions.push("m.created_at <= unixepoch(?)".to_string());
+            values.push(Value::Text(until.clone()));
+        }
+
+        (conditions, values)
+    }
+}
+
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Client-side state of a cached message.
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MessageFlags {
+    pub read: bool,
+    pub starred: bool,
+    pub archived: bool,
+    pub snoozed_until: Option<String>,
+}
+
+// 区分“未提供”和“显式为 null”，用于清除稍后提醒等可选设置
+pub(crate) fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    serde::Deserialize::deserialize(deserializer).map(Some)
+}
+
+/// Bulk change of message flags; fields left out are not touched. Setting
+/// `snoozed_until` to `null` clears the snooze.
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FlagUpdate {
+    pub read: Option<bool>,
+    pub starred: Option<bool>,
+    pub archived: Option<bool>,
+    #[serde(deserialize_with = "double_option")]
+    pub snoozed_until: Option<Option<String>>,
+}
+
+/// Unread messages per server, emitted to the frontend as `unread-count`.
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq)]
+pub struct UnreadCount {
+    pub total: u64,
+    pub by_server: HashMap<String, u64>,
+}
+
+/// Cached message as returned from the local store.
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct StoredMessage {
+    // 来源服务器记录在 message.server_id 中
+    #[serde(flatten)]
+    pub message: Message,
+    #[serde(flatten)]
+    pub flags: MessageFlags,
+}
+
+/// Embedded SQLite store keeping message history per server, so history
+/// stays available offline and after messages are deleted on the server.
+pub struct MessageStore {
+    conn: Mutex<Connection>,
+}
+
+impl MessageStore {
+    pub fn open(path: &Path) -> StoreResult<Self> {
+        info!("Opening message store: {:?}", path);
+        if let Err(e) = create_private(path) {
+            error!("Failed to restrict message store permissions: {}", e);
+        }
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> StoreResult<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    /// Opens the store at `path`, falling back to an in-memory database so
+    /// the app keeps working if the file cannot be used.
+    pub fn open_or_memory(path: &Path) -> Self {
+        match Self::open(path) {
+            Ok(store) => store,
+            Err(e) => {
+                error!("Failed to open message store, using in-memory store: {}", e);
+                Self::open_in_memory().expect("failed to open in-memory message store")
+            }
+        }
+    }
+
+    fn init(mut conn: Connection) -> StoreResult<Self> {
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        migrate(&mut conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Inserts or refreshes `messages` for `server_id`, returning how many
+    /// rows were written. Unchanged messages are skipped so their search index
+    /// entries are not rebuilt.
+    pub fn save_messages(&self, server_id: &str, messages: &[Message]) -> StoreResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut written = 0;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO messages (server_id, id, app_id, title, message, priority, timestamp, created_at, extras)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, unixepoch(?7), ?8)
+                 ON CONFLICT (server_id, id) DO UPDATE SET
+                     app_id = excluded.app_id,
+                     title = excluded.title,
+                     message = excluded.message,
+                     priority = excluded.priority,
+                     timestamp = excluded.timestamp,
+                     created_at = excluded.created_at,
+                     extras = excluded.extras
+                 WHERE app_id IS NOT excluded.app_id
+                     OR title IS NOT excluded.title
+                     OR message IS NOT excluded.message
+                     OR extras IS NOT excluded.extras
+                     OR priority IS NOT excluded.priority
+                     OR timestamp IS NOT excluded.timestamp",
+            )?;
+            for m in messages {
+                let extras = m.extras.as_ref().map(|e| e.to_string());
+                written += stmt.execute(params![
+                    server_id,
+                    m.id as i64,
+                    m.app_id as i64,
+                    m.title,
+                    m.message,
+                    m.priority,
+                    m.timestamp,
+                    extras,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(written)
+    }
+
+    pub fn query_messages(&self, query: &LocalMessageQuery) -> StoreResult<Vec<StoredMessage>> {
+        let (where_clause, mut values) = query.where_clause();
+        values.push(rusqlite::types::Value::Integer(
+            query.limit.map_or(-1, |l| l as i64),
+        ));
+        values.push(rusqlite::types::Value::Integer(
+            query.offset.unwrap_or(0) as i64
+        ));
+        // 各服务器的 id 相互独立，合并收件箱按时间排序
+        let sql = format!(
+            "SELECT {} FROM messages m {} ORDER BY m.created_at DESC, m.id DESC LIMIT ? OFFSET ?",
+            MESSAGE_COLUMNS, where_clause
+        );
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values), stored_message_from_row)?;
+        rows.collect()
+    }
+
+    pub fn count_messages(&self, query: &LocalMessageQuery) -> StoreResult<u64> {
+        let (where_clause, values) = query.where_clause();
+        let sql = format!("SELECT COUNT(*) FROM messages {}", where_clause);
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(&sql, params_from_iter(values), |row| row.get(0))?;
+        Ok(count as u64)
+    }
+
+    /// Caches application names so they can be matched by [`Self::search`].
+    pub fn save_applications(&self, server_id: &str, apps: &[Application]) -> StoreResult<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO applications (server_id, id, name) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (server_id, id) DO UPDATE SET name = excluded.name
+                 WHERE name IS NOT excluded.name",
+            )?;
+            for app in apps {
+                stmt.execute(params![server_id, app.id as i64, app.name])?;
+            }
+        }
+        tx.commit()
+    }
+
+    pub fn search(&self, query: &SearchQuery) -> StoreResult<Vec<StoredMessage>> {
+        let (conditions, mut values) = query.where_clause();
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        values.push(rusqlite::types::Value::Integer(
+            query.limit.map_or(-1, |l| l as i64),
+        ));
+        values.push(rusqlite::types::Value::Integer(
+            query.offset.unwrap_or(0) as i64
+        ));
+        let sql = format!(
+            "SELECT {} FROM messages m JOIN messages_fts f ON f.rowid = m.key
+             {} ORDER BY m.created_at DESC, m.id DESC LIMIT ? OFFSET ?",
+            MESSAGE_COLUMNS, where_clause
+        );
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values), stored_message_from_row)?;
+        rows.collect()
+    }
+
+    /// Applies `update` to the given messages, returning how many were changed.
+    pub fn update_flags(
+        &self,
+        server_id: &str,
+        ids: &[u64],
+        update: &FlagUpdate,
+    ) -> StoreResult<usize> {
+        use rusqlite::types::Value;
+
+        let mut assignments = Vec::new();
+        let mut values = Vec::new();
+        for (column, flag) in [
+            ("read = ?", update.read),
+            ("starred = ?", update.starred),
+            ("archived = ?", update.archived),
+        ] {
+            if let Some(flag) = flag {
+                assignments.push(column);
+                values.push(Value::Integer(flag as i64));
+            }
+        }
+        if let Some(snoozed_until) = &update.snoozed_until {
+            assignments.push("snoozed_until = unixepoch(?)");
+            values.push(snoozed_until.clone().map_or(Value::Null, Value::Text));
+        }
+        if assignments.is_empty() || ids.is_empty() {
+            return Ok(0);
+        }
+
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let sql = format!(
+            "UPDATE messages SET {} WHERE server_id = ? AND id IN ({})",
+            assignments.join(", "),
+            placeholders
+        );
+        values.push(Value::Text(server_id.to_string()));
+        values.extend(ids.iter().map(|id| Value::Integer(*id as i64)));
+
+        let conn = self.conn.lock().unwrap();
+        conn.execute(&sql, params_from_iter(values))
+    }
+
+    /// Marks every message as read, optionally only for one server.
+    pub fn mark_all_read(&self, server_id: Option<&str>) -> StoreResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE messages SET read = 1 WHERE read = 0 AND (?1 IS NULL OR server_id = ?1)",
+            params![server_id],
+        )
+    }
+
+    pub fn unread_count(&self) -> StoreResult<UnreadCount> {
+        let sql = format!(
+            "SELECT m.server_id, COUNT(*) FROM messages m WHERE {} GROUP BY m.server_id",
+            UNREAD_CONDITION
+        );
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+        })?;
+
+        let mut count = UnreadCount::default();
+        for row in rows {
+            let (server_id, unread) = row?;
+            count.total += unread;
+            count.by_server.insert(server_id, unread);
+        }
+        Ok(count)
+    }
+
+    pub fn delete_message(&self, server_id: &str, id: u64) -> StoreResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM messages WHERE server_id = ?1 AND id = ?2",
+            params![server_id, id as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes the cached messages of `server_id`, limited to one application
+    /// when `app_id` is given. Returns how many were deleted.
+    pub fn delete_messages(&self, server_id: &str, app_id: Option<u64>) -> StoreResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM messages WHERE server_id = ?1 AND (?2 IS NULL OR app_id = ?2)",
+            params![server_id, app_id.map(|id| id as i64)],
+        )
+    }
+}
+
+// 消息历史只允许当前用户读写：预先以 0600 创建数据库文件（SQLite 创建 WAL
+// 和共享内存文件时沿用它的权限），旧版本创建的文件则收紧权限
+fn create_private(path: &Path) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?;
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        crate::vault::restrict_permissions(Path::new(&file))?;
+    }
+    Ok(())
+}
+
+// 每个版本的 SQL 与 user_version 在同一事务中提交，中途失败不会留下半升级的数据库
+fn migrate(conn: &mut Connection) -> StoreResult<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version == 0 {
+        info!("Creating message store schema {}", MIGRATIONS.len());
+        let tx = conn.transaction()?;
+        tx.execute_batch(SCHEMA)?;
+        tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+        return tx.commit();
+    }
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+        info!("Applying message store migration {}", index + 1);
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn stored_message_from_row(row: &Row) -> StoreResult<StoredMessage> {
+    let extras: Option<String> = row.get(7)?;
+    let snoozed_until: Option<i64> = row.get(11)?;
+    Ok(StoredMessage {
+        message: Message {
+            id: row.get::<_, i64>(1)? as u64,
+            app_id: row.get::<_, i64>(2)? as u64,
+            title: row.get(3)?,
+            message: row.get(4)?,
+            priority: row.get(5)?,
+            timestamp: row.get(6)?,
+            extras: extras.and_then(|e| serde_json::from_str(&e).ok()),
+            server_id: Some(row.get(0)?),
+        },
+        flags: MessageFlags {
+            read: row.get(8)?,
+            starred: row.get(9)?,
+            archived: row.get(10)?,
+            snoozed_until: snoozed_until
+                .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                .map(|ts| ts.to_rfc3339()),
+        },
+    })
+}
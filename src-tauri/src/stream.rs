@@ -0,0 +1,730 @@
+use crate::gotify::{GotifyClient, GotifyError, Message, PagedMessages, AUTH_HEADER};
+use futures_util::{SinkExt, StreamExt, TryStreamExt};
+use log::{error, info, warn};
+use rand::Rng;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+#[cfg(not(unix))]
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::{self, Message as WsMessage};
+use tokio_tungstenite::{Connector, MaybeTlsStream};
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+// 流任务向外部（前端）投递事件的接口，便于与 Tauri 解耦
+pub trait StreamEvents: Send + Sync + 'static {
+    fn on_message(&self, message: Message);
+    fn on_status(&self, status: &ConnectionState);
+}
+
+/// Lifecycle of the WebSocket stream, reported to the frontend as the
+/// `connection-status` event.
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ConnectionState {
+    Disconnected,
+    Connecting,
+    Connected,
+    Reconnecting { attempt: u32, next_retry_at: String },
+    AuthFailed { reason: String },
+}
+
+impl ConnectionState {
+    pub fn label(&self) -> String {
+        match self {
+            Self::Disconnected => "未连接".to_string(),
+            Self::Connecting => "连接中".to_string(),
+            Self::Connected => "已连接".to_string(),
+            Self::Reconnecting { attempt, .. } => format!("重连中（第 {} 次）", attempt),
+            Self::AuthFailed { .. } => "认证失败".to_string(),
+        }
+    }
+
+    /// Combined label for several servers, e.g. for the tray tooltip.
+    pub fn summary_label<'a>(states: impl IntoIterator<Item = &'a ConnectionState>) -> String {
+        let states: Vec<&ConnectionState> = states.into_iter().collect();
+        match states.as_slice() {
+            [] => Self::Disconnected.label(),
+            [state] => state.label(),
+            _ => {
+                let connected = states
+                    .iter()
+                    .filter(|state| matches!(state, Self::Connected))
+                    .count();
+                format!("已连接 {}/{}", connected, states.len())
+            }
+        }
+    }
+}
+
+/// Payload of the `connection-status` event: the state of one server's stream.
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct ServerStatus {
+    pub server_id: String,
+    #[serde(flatten)]
+    pub status: ConnectionState,
+}
+
+/// Reconnect tuning for the stream, persisted as part of `AppSettings`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct StreamSettings {
+    pub reconnect_initial_delay_ms: u64,
+    pub reconnect_max_delay_ms: u64,
+    pub reconnect_multiplier: f64,
+    // 抖动比例，0.2 表示在计算出的延迟上下浮动 20%
+    pub reconnect_jitter: f64,
+    // 心跳间隔，0 表示关闭心跳和断线检测
+    pub ping_interval_secs: u64,
+    pub pong_timeout_secs: u64,
+}
+
+impl Default for StreamSettings {
+    fn default() -> Self {
+        Self {
+            reconnect_initial_delay_ms: 1_000,
+            reconnect_max_delay_ms: 300_000,
+            reconnect_multiplier: 2.0,
+            reconnect_jitter: 0.2,
+            ping_interval_secs: 30,
+            pong_timeout_secs: 15,
+        }
+    }
+}
+
+impl StreamSettings {
+    /// Delay before reconnect attempt `attempt` (starting at 1), before jitter.
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(32) as i32;
+        let delay = self.reconnect_initial_delay_ms as f64
+            * self.reconnect_multiplier.max(1.0).powi(exponent);
+        let max = self
+            .reconnect_max_delay_ms
+            .max(self.reconnect_initial_delay_ms);
+        Duration::from_millis(delay.min(max as f64) as u64)
+    }
+
+    pub fn ping_interval(&self) -> Option<Duration> {
+        (self.ping_interval_secs > 0).then(|| Duration::from_secs(self.ping_interval_secs))
+    }
+
+    /// How long the link may stay silent before it is considered dead: one
+    /// ping interval plus the time allowed for the pong to arrive.
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.ping_interval()
+            .map(|interval| interval + Duration::from_secs(self.pong_timeout_secs))
+    }
+
+    /// Applies jitter to `base_delay`; `sample` is a uniform value in `[0, 1)`.
+    pub fn delay_with_jitter(&self, attempt: u32, sample: f64) -> Duration {
+        let base = self.base_delay(attempt).as_millis() as f64;
+        let jitter = self.reconnect_jitter.clamp(0.0, 1.0);
+        let factor = 1.0 - jitter + 2.0 * jitter * sample;
+        let max = self
+            .reconnect_max_delay_ms
+            .max(self.reconnect_initial_delay_ms) as f64;
+        Duration::from_millis((base * factor).min(max) as u64)
+    }
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+pub struct StreamInfo {
+    pub server_id: String,
+    pub server_url: String,
+    pub started_at: String,
+    pub running: bool,
+    pub status: ConnectionState,
+}
+
+struct StreamHandle {
+    server_url: String,
+    started_at: String,
+    status: Arc<Mutex<ConnectionState>>,
+    wake: Arc<Notify>,
+    cancel: CancellationToken,
+    task: JoinHandle<()>,
+}
+
+impl StreamHandle {
+    fn info(&self, server_id: &str) -> StreamInfo {
+        StreamInfo {
+            server_id: server_id.to_string(),
+            server_url: self.server_url.clone(),
+            started_at: self.started_at.clone(),
+            running: !self.task.is_finished(),
+            status: self.status.lock().unwrap().clone(),
+        }
+    }
+}
+
+impl Drop for StreamHandle {
+    fn drop(&mut self) {
+        // 句柄被替换或清除时，保证对应的后台任务一定退出
+        self.cancel.cancel();
+    }
+}
+
+/// Owns the WebSocket stream tasks, one per connected server.
+///
+/// Starting a stream for a server that already has one cancels the previous
+/// task, so reconnects never leave a stale listener behind.
+#[derive(Default)]
+pub struct StreamSupervisor {
+    streams: Mutex<HashMap<String, StreamHandle>>,
+    // 每台服务器已投递的最大消息 id，重启任务后从这里继续补齐
+    positions: Mutex<HashMap<String, StreamPosition>>,
+}
+
+struct StreamPosition {
+    server_url: String,
+    last_id: Arc<Mutex<Option<u64>>>,
+}
+
+impl StreamSupervisor {
+    pub fn start<E: StreamEvents>(
+        &self,
+        server_id: &str,
+        client: GotifyClient,
+        settings: StreamSettings,
+        events: E,
+    ) {
+        let mut streams = self.streams.lock().unwrap();
+        if let Some(old) = streams.remove(server_id) {
+            info!("Stopping previous WebSocket task for {}", old.server_url);
+        }
+
+        let cancel = CancellationToken::new();
+        let status = Arc::new(Mutex::new(ConnectionState::Disconnected));
+        let wake = Arc::new(Notify::new());
+        let reporter = StatusReporter {
+            status: status.clone(),
+            events: Arc::new(events),
+        };
+        let server_url = client.get_base_url().to_string();
+        // 服务器地址变化后旧的 id 不再有意义，重新从最新消息开始
+        let last_id = {
+            let mut positions = self.positions.lock().unwrap();
+            let position =
+                positions
+                    .entry(server_id.to_string())
+                    .or_insert_with(|| StreamPosition {
+                        server_url: server_url.clone(),
+                        last_id: Arc::default(),
+                    });
+            if position.server_url != server_url {
+                position.server_url = server_url.clone();
+                position.last_id = Arc::default();
+            }
+            position.last_id.clone()
+        };
+        let task = tokio::spawn(run_listener(
+            client,
+            settings,
+            reporter,
+            last_id,
+            wake.clone(),
+            cancel.clone(),
+        ));
+
+        streams.insert(
+            server_id.to_string(),
+            StreamHandle {
+                server_url,
+                started_at: chrono::Utc::now().to_rfc3339(),
+                status,
+                wake,
+                cancel,
+                task,
+            },
+        );
+    }
+
+    /// Cancels the stream task of `server_id`, returning whether there was one.
+    pub fn stop(&self, server_id: &str) -> bool {
+        match self.streams.lock().unwrap().remove(server_id) {
+            Some(handle) => {
+                info!("Stopping WebSocket task for {}", handle.server_url);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels every stream task, returning how many were running.
+    pub fn stop_all(&self) -> usize {
+        let mut streams = self.streams.lock().unwrap();
+        let count = streams.len();
+        streams.clear();
+        count
+    }
+
+    /// Skips the current backoff wait of every stream, e.g. after the network
+    /// came back. Returns how many streams were waiting to reconnect.
+    pub fn reconnect_now(&self) -> usize {
+        self.streams
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|handle| {
+                let waiting = matches!(
+                    *handle.status.lock().unwrap(),
+                    ConnectionState::Reconnecting { .. }
+                );
+                // notify_one 会保留许可，任务报告状态后才开始等待时也不会错过
+                if waiting {
+                    handle.wake.notify_one();
+                }
+                waiting
+            })
+            .count()
+    }
+
+    /// Stream details of every server, ordered by server id.
+    pub fn infos(&self) -> Vec<StreamInfo> {
+        let mut infos: Vec<StreamInfo> = self
+            .streams
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(server_id, handle)| handle.info(server_id))
+            .collect();
+        infos.sort_by(|a, b| a.server_id.cmp(&b.server_id));
+        infos
+    }
+
+    pub fn status(&self, server_id: &str) -> ConnectionState {
+        self.streams
+            .lock()
+            .unwrap()
+            .get(server_id)
+            .map(|handle| handle.status.lock().unwrap().clone())
+            .unwrap_or(ConnectionState::Disconnected)
+    }
+
+    pub fn statuses(&self) -> HashMap<String, ConnectionState> {
+        self.streams
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(server_id, handle)| (server_id.clone(), handle.status.lock().unwrap().clone()))
+            .collect()
+    }
+}
+
+/// Remembers recently delivered message ids so that a message seen both via
+/// backfill and the live stream is only delivered once.
+pub struct MessageDeduper {
+    seen: HashSet<u64>,
+    order: VecDeque<u64>,
+    capacity: usize,
+}
+
+impl MessageDeduper {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            seen: HashSet::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Returns true the first time `id` is seen.
+    pub fn insert(&mut self, id: u64) -> bool {
+        if !self.seen.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+// 去重窗口大小，足以覆盖一次补齐加上流中重复推送的消息
+const DEDUPE_CAPACITY: usize = 1024;
+
+/// Single delivery path for stream and backfill messages: dedupe by id, then
+/// hand over to the `StreamEvents` processing stage.
+pub struct MessagePipeline<E> {
+    deduper: MessageDeduper,
+    events: Arc<E>,
+}
+
+impl<E: StreamEvents> MessagePipeline<E> {
+    pub fn new(events: Arc<E>) -> Self {
+        Self {
+            deduper: MessageDeduper::new(DEDUPE_CAPACITY),
+            events,
+        }
+    }
+
+    /// Delivers `message` unless it was already delivered, returning whether
+    /// it was passed on.
+    pub fn deliver(&mut self, message: Message) -> bool {
+        if !self.deduper.insert(message.id) {
+            info!("Skipping already delivered message: id={}", message.id);
+            return false;
+        }
+        self.events.on_message(message);
+        true
+    }
+}
+
+// 记录当前状态并转发给事件接收方
+struct StatusReporter<E> {
+    status: Arc<Mutex<ConnectionState>>,
+    events: Arc<E>,
+}
+
+impl<E: StreamEvents> StatusReporter<E> {
+    fn set(&self, status: ConnectionState) {
+        {
+            let mut current = self.status.lock().unwrap();
+            if *current == status {
+                return;
+            }
+            *current = status.clone();
+        }
+        info!("Stream status changed: {:?}", status);
+        self.events.on_status(&status);
+    }
+}
+
+// TCP 连接或 Unix 域套接字
+trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Transport for T {}
+
+type WsStream = tokio_tungstenite::WebSocketStream<MaybeTlsStream<Box<dyn Transport>>>;
+
+// 握手时通过请求头携带令牌，TLS 和代理设置与 REST 客户端一致
+async fn connect(
+    ws_url: &str,
+    client: &GotifyClient,
+) -> Result<(WsStream, tungstenite::handshake::client::Response), GotifyError> {
+    let mut request = ws_url.into_client_request().map_err(handshake_error)?;
+    // 反向代理的请求头同样要附加在握手请求上
+    request.headers_mut().extend(client.headers().clone());
+    let key = HeaderValue::from_str(client.get_token()).map_err(|e| {
+        handshake_error(tungstenite::Error::HttpFormat(
+            tungstenite::http::Error::from(e),
+        ))
+    })?;
+    request.headers_mut().insert(AUTH_HEADER, key);
+
+    let target = Url::parse(ws_url).map_err(|e| GotifyError::InvalidUrl(e.to_string()))?;
+    let secure = target.scheme() == "wss";
+    let host = target
+        .host_str()
+        .map(|h| h.trim_start_matches('[').trim_end_matches(']').to_string())
+        .ok_or_else(|| GotifyError::InvalidUrl(ws_url.to_string()))?;
+    let port = target
+        .port_or_known_default()
+        .unwrap_or(if secure { 443 } else { 80 });
+    let tcp: Box<dyn Transport> = match client.endpoint().unix_socket() {
+        Some(path) => Box::new(connect_unix(path).await?),
+        None => {
+            let proxy = client.proxy().resolve(&target)?;
+            if let Some(proxy) = &proxy {
+                info!(
+                    "Connecting to WebSocket through proxy {}:{}",
+                    proxy.host, proxy.port
+                );
+            }
+            Box::new(crate::proxy::connect(&host, port, proxy.as_ref()).await?)
+        }
+    };
+
+    let tls = client.tls();
+    match (&tls.pin, &tls.connector) {
+        (Some(pin), Some(connector)) if secure => {
+            let stream = tls_pinned(tcp, &host, connector.clone(), pin).await?;
+            tokio_tungstenite::client_async(request, stream)
+                .await
+                .map_err(handshake_error)
+        }
+        (_, connector) => tokio_tungstenite::client_async_tls_with_config(
+            request,
+            tcp,
+            None,
+            connector.clone().map(Connector::NativeTls),
+        )
+        .await
+        .map_err(handshake_error),
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: &Path) -> Result<tokio::net::UnixStream, GotifyError> {
+    tokio::net::UnixStream::connect(path).await.map_err(|e| {
+        GotifyError::StreamError(format!("cannot connect to {}: {}", path.display(), e))
+    })
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_path: &Path) -> Result<TcpStream, GotifyError> {
+    Err(GotifyError::InvalidUrl(
+        "Unix domain sockets are not supported on this platform".to_string(),
+    ))
+}
+
+// 固定证书时先完成 TLS 握手并核对指纹，确认无误后才发送携带令牌的升级请求
+async fn tls_pinned(
+    tcp: Box<dyn Transport>,
+    host: &str,
+    connector: native_tls::TlsConnector,
+    pin: &[u8; 32],
+) -> Result<MaybeTlsStream<Box<dyn Transport>>, GotifyError> {
+    let stream = tokio_native_tls::TlsConnector::from(connector)
+        .connect(host, tcp)
+        .await
+        .map_err(|e| GotifyError::CertificateVerification(e.to_string()))?;
+    let cert = stream
+        .get_ref()
+        .peer_certificate()
+        .ok()
+        .flatten()
+        .and_then(|cert| cert.to_der().ok());
+    crate::tls::check_pin(pin, cert.as_deref())?;
+    Ok(MaybeTlsStream::NativeTls(stream))
+}
+
+// 将握手失败转换为 GotifyError，认证类错误映射为 AuthFailed
+pub fn handshake_error(err: tungstenite::Error) -> GotifyError {
+    match err {
+        tungstenite::Error::Http(response) => {
+            let body = response
+                .body()
+                .as_ref()
+                .map(|b| String::from_utf8_lossy(b).to_string())
+                .unwrap_or_default();
+            GotifyError::from_status_code(response.status(), body)
+        }
+        other => crate::tls::certificate_error(&other)
+            .unwrap_or_else(|| GotifyError::StreamError(other.to_string())),
+    }
+}
+
+fn parse_message(text: &str) -> Option<Message> {
+    info!("WebSocket received text: {}", text);
+    match serde_json::from_str::<Message>(text) {
+        Ok(message) => {
+            info!("Received message via WebSocket: id={}", message.id);
+            Some(message)
+        }
+        Err(e) => {
+            warn!("Failed to parse message from JSON: {}", e);
+            None
+        }
+    }
+}
+
+// 关闭心跳时计时器使用的占位周期
+const ONE_DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+// 断线补齐时每页拉取的消息数
+const BACKFILL_PAGE_SIZE: u64 = 100;
+
+/// Keeps the messages of one `/message` page that are newer than `after`.
+///
+/// Returns the kept messages and whether paging should continue, i.e. there
+/// is a next page and this one did not yet reach a message at or below `after`.
+pub fn newer_than(after: u64, page: PagedMessages) -> (Vec<Message>, bool) {
+    let reached = page.messages.iter().any(|m| m.id <= after);
+    let more = page.paging.next.is_some() && !reached;
+    let newer = page.messages.into_iter().filter(|m| m.id > after).collect();
+    (newer, more)
+}
+
+// 拉取 id 大于 after 的全部消息，按 id 升序返回
+async fn fetch_missed(client: &GotifyClient, after: u64) -> Result<Vec<Message>, GotifyError> {
+    let mut missed: Vec<Message> = Vec::new();
+    let mut pages = std::pin::pin!(client.message_pages(None, BACKFILL_PAGE_SIZE));
+    while let Some(page) = pages.try_next().await? {
+        let (newer, more) = newer_than(after, page);
+        missed.extend(newer);
+        if !more {
+            break;
+        }
+    }
+    missed.sort_by_key(|m| m.id);
+    missed.dedup_by_key(|m| m.id);
+    Ok(missed)
+}
+
+// 连接建立后的同步：首次连接只记录最新 id，重连时返回错过的消息和新的最大 id
+async fn catch_up(
+    client: &GotifyClient,
+    last_id: Option<u64>,
+) -> Result<(Vec<Message>, Option<u64>), GotifyError> {
+    match last_id {
+        Some(after) => {
+            let missed = fetch_missed(client, after).await?;
+            let latest = missed.last().map_or(after, |m| m.id);
+            Ok((missed, Some(latest)))
+        }
+        None => {
+            let latest = client.get_messages(None, Some(1), None).await?;
+            Ok((
+                Vec::new(),
+                Some(latest.messages.first().map_or(0, |m| m.id)),
+            ))
+        }
+    }
+}
+
+async fn run_listener<E: StreamEvents>(
+    client: GotifyClient,
+    settings: StreamSettings,
+    events: StatusReporter<E>,
+    position: Arc<Mutex<Option<u64>>>,
+    wake: Arc<Notify>,
+    cancel: CancellationToken,
+) {
+    info!("Starting WebSocket task...");
+    let ws_url = client.stream_url();
+    info!("Connecting to WebSocket: {}", ws_url);
+
+    let mut attempt: u32 = 0;
+    // 已投递的最大消息 id，用于重连后补齐和去重；任务重启时沿用上一个任务的位置
+    let mut last_id: Option<u64> = *position.lock().unwrap();
+    let mut pipeline = MessagePipeline::new(events.events.clone());
+
+    loop {
+        if attempt == 0 {
+            events.set(ConnectionState::Connecting);
+        }
+
+        let connected = tokio::select! {
+            _ = cancel.cancelled() => break,
+            result = connect(&ws_url, &client) => result,
+        };
+
+        match connected {
+            Ok((ws_stream, response)) => {
+                info!("WebSocket connected, status: {:?}", response.status());
+                events.set(ConnectionState::Connected);
+                attempt = 0;
+                let (mut write, mut read) = ws_stream.split();
+
+                // 先补齐断线期间错过的消息，再处理流中的新消息
+                let caught_up = tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    result = catch_up(&client, last_id) => result,
+                };
+                match caught_up {
+                    Ok((missed, latest)) => {
+                        if !missed.is_empty() {
+                            info!("Backfilling {} missed messages", missed.len());
+                        }
+                        for message in missed {
+                            pipeline.deliver(message);
+                        }
+                        last_id = latest;
+                        *position.lock().unwrap() = last_id;
+                    }
+                    Err(e) => warn!("Failed to backfill missed messages: {}", e),
+                }
+
+                // 定期发送 ping，任何入站帧都会刷新存活期限
+                let ping_interval = settings.ping_interval().unwrap_or(Duration::MAX);
+                let read_timeout = settings.read_timeout().unwrap_or(Duration::MAX);
+                let keepalive = settings.ping_interval().is_some();
+                let mut ping_timer = tokio::time::interval_at(
+                    tokio::time::Instant::now() + ping_interval.min(ONE_DAY),
+                    ping_interval.min(ONE_DAY),
+                );
+                let mut deadline = tokio::time::Instant::now() + read_timeout.min(ONE_DAY);
+
+                loop {
+                    let next = tokio::select! {
+                        _ = cancel.cancelled() => break,
+                        _ = ping_timer.tick(), if keepalive => {
+                            if let Err(e) = write.send(WsMessage::Ping(Default::default())).await {
+                                error!("Failed to send WebSocket ping: {}", e);
+                                break;
+                            }
+                            continue;
+                        }
+                        _ = tokio::time::sleep_until(deadline), if keepalive => {
+                            warn!(
+                                "No data received for {:?}, treating connection as dead",
+                                read_timeout
+                            );
+                            break;
+                        }
+                        next = read.next() => next,
+                    };
+                    deadline = tokio::time::Instant::now() + read_timeout.min(ONE_DAY);
+
+                    match next {
+                        Some(Ok(msg)) => {
+                            if msg.is_text() {
+                                let Some(message) = parse_message(msg.to_text().unwrap_or(""))
+                                else {
+                                    continue;
+                                };
+                                last_id = last_id.max(Some(message.id));
+                                *position.lock().unwrap() = last_id;
+                                pipeline.deliver(message);
+                            } else if msg.is_close() {
+                                info!("WebSocket close message received");
+                                break;
+                            }
+                        }
+                        Some(Err(e)) => {
+                            error!("WebSocket error: {}", e);
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+
+                if cancel.is_cancelled() {
+                    break;
+                }
+                info!("WebSocket connection closed");
+            }
+            Err(e) => match e {
+                GotifyError::AuthFailed(reason) => {
+                    // 令牌已失效时继续重试没有意义，直接停止
+                    error!("WebSocket handshake rejected: {}, giving up", reason);
+                    events.set(ConnectionState::AuthFailed { reason });
+                    return;
+                }
+                e => error!("WebSocket connection failed: {}", e),
+            },
+        }
+
+        attempt += 1;
+        let delay = settings.delay_with_jitter(attempt, rand::thread_rng().gen::<f64>());
+        let next_retry_at =
+            chrono::Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+        info!("Reconnecting in {:?} (attempt {})", delay, attempt);
+        events.set(ConnectionState::Reconnecting {
+            attempt,
+            next_retry_at: next_retry_at.to_rfc3339(),
+        });
+
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(delay) => {}
+            _ = wake.notified() => {
+                info!("Reconnect requested, retrying immediately");
+                attempt = 0;
+            }
+        }
+    }
+
+    info!("WebSocket task stopped");
+}
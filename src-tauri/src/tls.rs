@@ -0,0 +1,318 @@
+use crate::gotify::GotifyError;
+use native_tls::{Certificate, Identity, TlsConnector};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, OtherError, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::error::Error as StdError;
+use std::sync::Arc;
+
+const PEM_CERT_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_CERT_END: &str = "-----END CERTIFICATE-----";
+
+/// Per-server TLS trust settings, shared by the REST client and the
+/// WebSocket stream. Files are read whenever a client is built, so renewed
+/// certificates are picked up on the next connect.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TlsOptions {
+    // 额外信任的 CA 证书（PEM，可包含多张）
+    #[serde(default)]
+    pub ca_file: Option<String>,
+    // 服务器证书的 SHA-256 指纹，设置后只认该证书，可用于自签名证书
+    #[serde(default)]
+    pub pin_sha256: Option<String>,
+    // 双向 TLS 的客户端证书和 PKCS#8 私钥（PEM）
+    #[serde(default)]
+    pub client_cert_file: Option<String>,
+    #[serde(default)]
+    pub client_key_file: Option<String>,
+}
+
+/// TLS settings resolved from [`TlsOptions`].
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    // 未配置任何选项时为 None，使用系统默认设置
+    pub connector: Option<TlsConnector>,
+    pub pin: Option<[u8; 32]>,
+    // 固定证书时 REST 客户端使用的 rustls 配置，握手时即核对指纹
+    pub pinned: Option<rustls::ClientConfig>,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn read_file(kind: &str, path: &str) -> Result<Vec<u8>, GotifyError> {
+    std::fs::read(path)
+        .map_err(|e| GotifyError::TlsConfig(format!("cannot read {} {}: {}", kind, path, e)))
+}
+
+impl TlsOptions {
+    pub fn is_default(&self) -> bool {
+        non_empty(&self.ca_file).is_none()
+            && non_empty(&self.pin_sha256).is_none()
+            && non_empty(&self.client_cert_file).is_none()
+            && non_empty(&self.client_key_file).is_none()
+    }
+
+    pub fn build(&self) -> Result<TlsConfig, GotifyError> {
+        if self.is_default() {
+            return Ok(TlsConfig::default());
+        }
+
+        let mut builder = TlsConnector::builder();
+        let mut client_identity = None;
+        if let Some(path) = non_empty(&self.ca_file) {
+            let pem = read_file("CA file", path)?;
+            let certs = certificates_from_pem(&pem)?;
+            if certs.is_empty() {
+                return Err(GotifyError::TlsConfig(format!(
+                    "no certificate found in {}",
+                    path
+                )));
+            }
+            for cert in certs {
+                builder.add_root_certificate(cert);
+            }
+        }
+
+        match (
+            non_empty(&self.client_cert_file),
+            non_empty(&self.client_key_file),
+        ) {
+            (Some(cert_path), Some(key_path)) => {
+                let cert = read_file("client certificate", cert_path)?;
+                let key = read_file("client key", key_path)?;
+                let identity = Identity::from_pkcs8(&cert, &key).map_err(|e| {
+                    GotifyError::TlsConfig(format!(
+                        "invalid client certificate or key (the key must be PKCS#8 PEM): {}",
+                        e
+                    ))
+                })?;
+                builder.identity(identity);
+                client_identity = Some((cert, key));
+            }
+            (None, None) => {}
+            _ => {
+                return Err(GotifyError::TlsConfig(
+                    "client certificate and key must be set together".to_string(),
+                ))
+            }
+        }
+
+        let pin = match non_empty(&self.pin_sha256) {
+            Some(pin) => Some(parse_pin(pin)?),
+            None => None,
+        };
+        // 固定证书时由指纹确认服务器身份，自签名证书和主机名不匹配都可以接受
+        if pin.is_some() {
+            builder.danger_accept_invalid_certs(true);
+            builder.danger_accept_invalid_hostnames(true);
+        }
+
+        let pinned = match &pin {
+            Some(pin) => Some(pinned_client_config(*pin, client_identity)?),
+            None => None,
+        };
+        let connector = builder
+            .build()
+            .map_err(|e| GotifyError::TlsConfig(e.to_string()))?;
+        Ok(TlsConfig {
+            connector: Some(connector),
+            pin,
+            pinned,
+        })
+    }
+}
+
+// 只接受指纹匹配的服务器证书；签名仍按正常流程校验，证明对方持有该证书的私钥
+#[derive(Debug)]
+struct PinVerifier {
+    pin: [u8; 32],
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        check_pin(&self.pin, Some(end_entity.as_ref()))
+            .map(|_| ServerCertVerified::assertion())
+            .map_err(|e| {
+                rustls::Error::InvalidCertificate(CertificateError::Other(OtherError(Arc::new(e))))
+            })
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+// 固定证书时的 rustls 配置：指纹在 TLS 握手中核对，不匹配时不会发出任何 HTTP 数据
+fn pinned_client_config(
+    pin: [u8; 32],
+    identity: Option<(Vec<u8>, Vec<u8>)>,
+) -> Result<rustls::ClientConfig, GotifyError> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| GotifyError::TlsConfig(e.to_string()))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinVerifier { pin, provider }));
+    match identity {
+        Some((cert, key)) => {
+            let invalid = |e: &dyn std::fmt::Display| {
+                GotifyError::TlsConfig(format!("invalid client certificate or key: {}", e))
+            };
+            let certs = CertificateDer::pem_slice_iter(&cert)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| invalid(&e))?;
+            let key = PrivateKeyDer::from_pem_slice(&key).map_err(|e| invalid(&e))?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|e| invalid(&e))
+        }
+        None => Ok(builder.with_no_client_auth()),
+    }
+}
+
+fn certificates_from_pem(pem: &[u8]) -> Result<Vec<Certificate>, GotifyError> {
+    let text = String::from_utf8_lossy(pem);
+    let mut certs = Vec::new();
+    let mut rest = text.as_ref();
+    while let Some(start) = rest.find(PEM_CERT_BEGIN) {
+        let Some(len) = rest[start..].find(PEM_CERT_END) else {
+            break;
+        };
+        let end = start + len + PEM_CERT_END.len();
+        let cert = Certificate::from_pem(&rest.as_bytes()[start..end])
+            .map_err(|e| GotifyError::TlsConfig(format!("invalid CA certificate: {}", e)))?;
+        certs.push(cert);
+        rest = &rest[end..];
+    }
+    Ok(certs)
+}
+
+/// Parses a SHA-256 fingerprint written as hex, with or without `:`
+/// separators (as printed by `openssl x509 -fingerprint -sha256`).
+pub fn parse_pin(pin: &str) -> Result<[u8; 32], GotifyError> {
+    let pin = pin.trim();
+    let pin = pin
+        .strip_prefix("sha256/")
+        .or_else(|| pin.strip_prefix("SHA256:"))
+        .unwrap_or(pin);
+    let digits: Vec<u8> = pin.bytes().filter(|b| !matches!(b, b':' | b' ')).collect();
+    let invalid = || GotifyError::TlsConfig(format!("invalid SHA-256 fingerprint: {}", pin));
+    if digits.len() != 64 {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; 32];
+    for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Colon separated upper-case SHA-256 fingerprint of a DER certificate.
+pub fn fingerprint(der: &[u8]) -> String {
+    to_hex(&Sha256::digest(der))
+}
+
+/// Checks the server certificate (DER) against the configured pin.
+pub fn check_pin(pin: &[u8; 32], der: Option<&[u8]>) -> Result<(), GotifyError> {
+    let expected = to_hex(pin);
+    match der {
+        Some(der) if Sha256::digest(der).as_slice() == pin => Ok(()),
+        Some(der) => Err(GotifyError::CertificatePinMismatch {
+            expected,
+            actual: fingerprint(der),
+        }),
+        None => Err(GotifyError::CertificatePinMismatch {
+            expected,
+            actual: "no certificate".to_string(),
+        }),
+    }
+}
+
+/// Finds a TLS failure in an error's source chain, e.g. a certificate the
+/// system (or the configured CA) does not trust.
+pub fn certificate_error(err: &(dyn StdError + 'static)) -> Option<GotifyError> {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(tls) = err.downcast_ref::<native_tls::Error>() {
+            return Some(GotifyError::CertificateVerification(tls.to_string()));
+        }
+        if let Some(tls) = err.downcast_ref::<rustls::Error>() {
+            return Some(rustls_error(tls));
+        }
+        // io::Error 的 source() 会跳过被包装的错误本身
+        source = match err
+            .downcast_ref::<std::io::Error>()
+            .and_then(|e| e.get_ref())
+        {
+            Some(inner) => Some(inner as &(dyn StdError + 'static)),
+            None => err.source(),
+        };
+    }
+    None
+}
+
+fn rustls_error(err: &rustls::Error) -> GotifyError {
+    if let rustls::Error::InvalidCertificate(CertificateError::Other(other)) = err {
+        if let Some(GotifyError::CertificatePinMismatch { expected, actual }) =
+            other.0.downcast_ref::<GotifyError>()
+        {
+            return GotifyError::CertificatePinMismatch {
+                expected: expected.clone(),
+                actual: actual.clone(),
+            };
+        }
+    }
+    GotifyError::CertificateVerification(err.to_string())
+}
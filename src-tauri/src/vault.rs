@@ -0,0 +1,477 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use thiserror::Error;
+
+// 配置文件中以此前缀表示令牌保存在保险库中，后面是服务器配置的 id
+const REFERENCE_PREFIX: &str = "vault:";
+const VAULT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+/// PBKDF2-HMAC-SHA256 rounds for user passphrases.
+pub const PASSPHRASE_ITERATIONS: u32 = 600_000;
+/// Key files and Secret Service keys are random, so one round is enough.
+pub const KEY_ITERATIONS: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum VaultError {
+    #[error("Token vault is locked; unlock it with the passphrase first")]
+    Locked,
+    #[error("Cannot decrypt the token vault: wrong passphrase or key")]
+    WrongKey,
+    #[error("Token vault I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid token vault: {0}")]
+    Invalid(String),
+    #[error("Secret Service error: {0}")]
+    SecretService(String),
+}
+
+/// Where the key protecting stored tokens comes from.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultMode {
+    // 令牌以明文保存在配置文件中
+    #[default]
+    Plaintext,
+    // 每次启动时输入密码解锁
+    Passphrase,
+    // 从密钥文件读取
+    KeyFile,
+    // 密钥保存在系统密钥环（Secret Service）中
+    SecretService,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct VaultSettings {
+    #[serde(default)]
+    pub mode: VaultMode,
+    #[serde(default)]
+    pub key_file: Option<String>,
+}
+
+// 保险库文件内容，令牌表整体以 AES-256-GCM 加密
+#[derive(serde::Serialize, serde::Deserialize)]
+struct VaultFile {
+    version: u32,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// An unlocked token vault: the file it lives in and the derived key.
+#[derive(Clone)]
+pub struct Vault {
+    path: PathBuf,
+    key: [u8; KEY_LEN],
+    salt: [u8; SALT_LEN],
+    iterations: u32,
+}
+
+impl std::fmt::Debug for Vault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Vault").field("path", &self.path).finish()
+    }
+}
+
+/// Reference stored in the config in place of the token of server `id`.
+pub fn reference(id: &str) -> String {
+    format!("{}{}", REFERENCE_PREFIX, id)
+}
+
+/// The server id a token reference points to, if `token` is one.
+pub fn reference_id(token: &str) -> Option<&str> {
+    token.strip_prefix(REFERENCE_PREFIX)
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], VaultError> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| VaultError::Invalid("no secure random source".to_string()))?;
+    Ok(bytes)
+}
+
+fn derive_key(secret: &[u8], salt: &[u8], iterations: u32) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    let iterations = NonZeroU32::new(iterations).unwrap_or(NonZeroU32::MIN);
+    ring::pbkdf2::derive(
+        ring::pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        secret,
+        &mut key,
+    );
+    key
+}
+
+fn decode<const N: usize>(field: &str, value: &str) -> Result<[u8; N], VaultError> {
+    BASE64
+        .decode(value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| VaultError::Invalid(format!("bad {}", field)))
+}
+
+impl Vault {
+    /// Derives a new key with a fresh salt. Nothing is written until tokens
+    /// are sealed, which replaces any previous vault file.
+    pub fn create(path: PathBuf, secret: &[u8], iterations: u32) -> Result<Self, VaultError> {
+        let salt = random_bytes::<SALT_LEN>()?;
+        Ok(Self {
+            key: derive_key(secret, &salt, iterations),
+            path,
+            salt,
+            iterations,
+        })
+    }
+
+    /// Opens the vault at `path`, checking `secret` by decrypting it. A
+    /// missing file is created with `iterations` on the next seal.
+    pub fn open_or_create(
+        path: PathBuf,
+        secret: &[u8],
+        iterations: u32,
+    ) -> Result<Self, VaultError> {
+        let Some(file) = read_vault_file(&path)? else {
+            return Self::create(path, secret, iterations);
+        };
+        let salt = decode::<SALT_LEN>("salt", &file.salt)?;
+        let vault = Self {
+            key: derive_key(secret, &salt, file.iterations),
+            path,
+            salt,
+            iterations: file.iterations,
+        };
+        vault.decrypt(&file)?;
+        Ok(vault)
+    }
+
+    fn cipher(&self) -> LessSafeKey {
+        LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &self.key).expect("AES-256 key length"))
+    }
+
+    fn decrypt(&self, file: &VaultFile) -> Result<BTreeMap<String, String>, VaultError> {
+        if file.version != VAULT_VERSION {
+            return Err(VaultError::Invalid(format!(
+                "unsupported version {}",
+                file.version
+            )));
+        }
+        let nonce = Nonce::assume_unique_for_key(decode::<NONCE_LEN>("nonce", &file.nonce)?);
+        let mut data = BASE64
+            .decode(&file.ciphertext)
+            .map_err(|_| VaultError::Invalid("bad ciphertext".to_string()))?;
+        let plain = self
+            .cipher()
+            .open_in_place(nonce, Aad::empty(), &mut data)
+            .map_err(|_| VaultError::WrongKey)?;
+        serde_json::from_slice(plain).map_err(|e| VaultError::Invalid(e.to_string()))
+    }
+
+    /// Decrypted tokens by server id; empty if the vault was never written.
+    pub fn tokens(&self) -> Result<BTreeMap<String, String>, VaultError> {
+        match read_vault_file(&self.path)? {
+            Some(file) => self.decrypt(&file),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    fn write_tokens(&self, tokens: &BTreeMap<String, String>) -> Result<(), VaultError> {
+        let nonce = random_bytes::<NONCE_LEN>()?;
+        let mut data =
+            serde_json::to_vec(tokens).map_err(|e| VaultError::Invalid(e.to_string()))?;
+        self.cipher()
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+            .map_err(|_| VaultError::Invalid("encryption failed".to_string()))?;
+        let file = VaultFile {
+            version: VAULT_VERSION,
+            iterations: self.iterations,
+            salt: BASE64.encode(self.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(data),
+        };
+        let json =
+            serde_json::to_vec_pretty(&file).map_err(|e| VaultError::Invalid(e.to_string()))?;
+        write_private(&self.path, &json)?;
+        Ok(())
+    }
+
+    /// Moves the tokens of `(server id, token)` pairs into the vault and
+    /// replaces them with references. The vault then holds exactly these
+    /// servers, so tokens of deleted servers are dropped.
+    pub fn seal<'a>(
+        &self,
+        entries: impl IntoIterator<Item = (&'a str, &'a mut String)>,
+    ) -> Result<(), VaultError> {
+        // 只有遇到引用时才需要读取旧的保险库
+        let mut stored: Option<BTreeMap<String, String>> = None;
+        let mut tokens = BTreeMap::new();
+        let mut sealed = Vec::new();
+        for (id, token) in entries {
+            let value = match reference_id(token) {
+                Some(reference) => {
+                    if stored.is_none() {
+                        stored = Some(self.tokens()?);
+                    }
+                    // 找不到对应令牌的引用保持原样
+                    match stored.as_ref().and_then(|s| s.get(reference)) {
+                        Some(value) => value.clone(),
+                        None => continue,
+                    }
+                }
+                None if token.is_empty() => continue,
+                None => token.clone(),
+            };
+            tokens.insert(id.to_string(), value);
+            sealed.push((id, token));
+        }
+        self.write_tokens(&tokens)?;
+        for (id, token) in sealed {
+            *token = reference(id);
+        }
+        Ok(())
+    }
+
+    /// Replaces token references with the tokens stored in the vault.
+    pub fn open_tokens<'a>(
+        &self,
+        entries: impl IntoIterator<Item = &'a mut String>,
+    ) -> Result<(), VaultError> {
+        let stored = self.tokens()?;
+        for token in entries {
+            if let Some(value) = reference_id(token).and_then(|id| stored.get(id)) {
+                crate::logging::register_secret(value);
+                *token = value.clone();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_vault_file(path: &Path) -> Result<Option<VaultFile>, VaultError> {
+    match std::fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| VaultError::Invalid(e.to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Atomically writes a file only the current user can read (0600 on Unix).
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&tmp)?;
+    // 临时文件可能已存在，mode 只对新建文件生效
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp, path)
+}
+
+/// Makes an existing file readable by the current user only.
+pub fn restrict_permissions(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        match std::fs::metadata(path) {
+            Ok(meta) if meta.permissions().mode() & 0o077 != 0 => {
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Reads a key file, creating one with 32 random bytes if it does not exist.
+pub fn read_or_create_key_file(path: &Path) -> Result<Vec<u8>, VaultError> {
+    match std::fs::read(path) {
+        Ok(key) if key.is_empty() => Err(VaultError::Invalid(format!(
+            "key file {} is empty",
+            path.display()
+        ))),
+        Ok(key) => Ok(key),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let key = random_bytes::<KEY_LEN>()?;
+            write_private(path, &key)?;
+            Ok(key.to_vec())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn key_file_path(settings: &VaultSettings) -> Result<&Path, VaultError> {
+    settings
+        .key_file
+        .as_deref()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(Path::new)
+        .ok_or_else(|| VaultError::Invalid("no key file configured".to_string()))
+}
+
+/// Creates a new vault key for `settings`; `None` for plaintext storage.
+pub fn create(
+    settings: &VaultSettings,
+    passphrase: Option<&str>,
+    path: PathBuf,
+) -> Result<Option<Vault>, VaultError> {
+    let vault = match settings.mode {
+        VaultMode::Plaintext => return Ok(None),
+        VaultMode::Passphrase => {
+            let passphrase = passphrase
+                .filter(|p| !p.is_empty())
+                .ok_or_else(|| VaultError::Invalid("a passphrase is required".to_string()))?;
+            Vault::create(path, passphrase.as_bytes(), PASSPHRASE_ITERATIONS)?
+        }
+        VaultMode::KeyFile => {
+            let key = read_or_create_key_file(key_file_path(settings)?)?;
+            Vault::create(path, &key, KEY_ITERATIONS)?
+        }
+        VaultMode::SecretService => {
+            let key = BASE64.encode(random_bytes::<KEY_LEN>()?);
+            secret_service::store(&key)?;
+            Vault::create(path, key.as_bytes(), KEY_ITERATIONS)?
+        }
+    };
+    Ok(Some(vault))
+}
+
+/// Unlocks the vault at startup when its key needs no user input (key file
+/// or Secret Service). Passphrase vaults stay locked until [`unlock`].
+pub fn unlock_without_prompt(settings: &VaultSettings, path: PathBuf) -> Result<(), VaultError> {
+    let key = match settings.mode {
+        VaultMode::Plaintext | VaultMode::Passphrase => return Ok(()),
+        VaultMode::KeyFile => std::fs::read(key_file_path(settings)?)?,
+        VaultMode::SecretService => secret_service::lookup()?.into_bytes(),
+    };
+    set_unlocked(Some(Vault::open_or_create(path, &key, KEY_ITERATIONS)?));
+    Ok(())
+}
+
+/// Unlocks a passphrase vault for the rest of the session.
+pub fn unlock(passphrase: &str, path: PathBuf) -> Result<(), VaultError> {
+    let vault = Vault::open_or_create(path, passphrase.as_bytes(), PASSPHRASE_ITERATIONS)?;
+    set_unlocked(Some(vault));
+    Ok(())
+}
+
+// 解锁后的保险库在本次运行期间保存在内存中
+fn unlocked_vault() -> &'static RwLock<Option<Vault>> {
+    static VAULT: OnceLock<RwLock<Option<Vault>>> = OnceLock::new();
+    VAULT.get_or_init(|| RwLock::new(None))
+}
+
+pub fn unlocked() -> Option<Vault> {
+    unlocked_vault().read().unwrap().clone()
+}
+
+pub fn set_unlocked(vault: Option<Vault>) {
+    *unlocked_vault().write().unwrap() = vault;
+}
+
+/// Stores the vault key in the desktop keyring through `secret-tool`
+/// (libsecret), which is available on most Linux desktops.
+pub mod secret_service {
+    use super::VaultError;
+
+    const ATTRIBUTES: [&str; 4] = ["service", "gotify-desktop", "kind", "vault-key"];
+
+    #[cfg(target_os = "linux")]
+    fn secret_tool(args: &[&str], input: Option<&str>) -> Result<String, VaultError> {
+        use std::io::Write;
+        use std::process::{Command, Stdio};
+
+        let mut child = Command::new("secret-tool")
+            .args(args)
+            .args(ATTRIBUTES)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                VaultError::SecretService(format!("secret-tool is not available: {}", e))
+            })?;
+        if let Some(input) = input {
+            child
+                .stdin
+                .take()
+                .expect("piped stdin")
+                .write_all(input.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(VaultError::SecretService(if stderr.trim().is_empty() {
+                "no vault key in the keyring".to_string()
+            } else {
+                stderr.trim().to_string()
+            }));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn secret_tool(_args: &[&str], _input: Option<&str>) -> Result<String, VaultError> {
+        Err(VaultError::SecretService(
+            "the Secret Service API is only available on Linux".to_string(),
+        ))
+    }
+
+    pub fn is_available() -> bool {
+        cfg!(target_os = "linux")
+            && std::process::Command::new("secret-tool")
+                .arg("--help")
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .status()
+                .is_ok()
+    }
+
+    pub fn store(key: &str) -> Result<(), VaultError> {
+        secret_tool(&["store", "--label=Gotify Desktop token vault"], Some(key)).map(|_| ())
+    }
+
+    pub fn lookup() -> Result<String, VaultError> {
+        let key = secret_tool(&["lookup"], None)?;
+        if key.is_empty() {
+            return Err(VaultError::SecretService(
+                "no vault key in the keyring".to_string(),
+            ));
+        }
+        Ok(key)
+    }
+
+    pub fn clear() -> Result<(), VaultError> {
+        secret_tool(&["clear"], None).map(|_| ())
+    }
+}
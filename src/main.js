@@ -11,8 +11,15 @@ const AppState = {
     error: null,
     loadingMore: false,
     hasMoreMessages: true,
+    // 下一页请求使用的 since，来自服务器返回的 paging
+    nextSince: null,
     configs: [],
     currentConfigId: null,
+    // 当前查看的服务器标识（ServerConfig.id 或临时连接的服务器地址）
+    serverId: null,
+    connectionStatus: { state: 'disconnected' },
+    // 所有已连接服务器的状态，按服务器标识索引
+    connectionStatuses: {},
     settings: {
         enable_autostart: false,
         minimize_to_tray: false,
@@ -22,6 +29,28 @@ const AppState = {
     }
 };
 
+// 连接状态文字
+function connectionStatusLabel(status) {
+    switch (status.state) {
+        case 'connecting':
+            return '连接中...';
+        case 'connected':
+            return '已连接';
+        case 'reconnecting':
+            return `重连中（第 ${status.attempt} 次）`;
+        case 'auth_failed':
+            return '认证失败';
+        default:
+            return '未连接';
+    }
+}
+
+// 消息来源服务器的显示名称
+function serverName(serverId) {
+    const config = AppState.configs.find(c => c.id === serverId);
+    return config ? config.name : serverId;
+}
+
 // 更新UI状态的辅助函数
 function updateUIState(updates) {
     Object.assign(AppState, updates);
@@ -33,8 +62,9 @@ function render() {
     const statusIndicator = document.getElementById('statusIndicator');
     const statusText = document.getElementById('statusText');
     if (statusIndicator && statusText) {
-        statusIndicator.className = AppState.connected ? 'status connected' : 'status disconnected';
-        statusText.textContent = AppState.connected ? '已连接' : '未连接';
+        const streamOk = AppState.connectionStatus.state === 'connected';
+        statusIndicator.className = AppState.connected && streamOk ? 'status connected' : 'status disconnected';
+        statusText.textContent = AppState.connected ? connectionStatusLabel(AppState.connectionStatus) : '未连接';
     }
 
     // 更新配置名称和服务器地址显示
@@ -90,7 +120,7 @@ function renderMessages() {
             <button id="refreshBtn" onclick="refreshMessages()">刷新</button>
         </div>
         ${AppState.messages.map(m => `
-            <div class="message-card priority-${m.priority}" data-id="${m.id}">
+            <div class="message-card priority-${m.priority}" data-id="${m.id}" data-server="${escapeHtml(m.server_id || '')}">
                 <div class="message-header">
                     <span class="message-title">${escapeHtml(m.title || '无标题')}</span>
                     <span class="message-time">${formatTime(m.timestamp)}</span>
@@ -98,7 +128,8 @@ function renderMessages() {
                 <div class="message-body">${escapeHtml(m.message)}</div>
                 <div class="message-footer">
                     <span class="priority-badge priority-${m.priority}">优先级: ${m.priority}</span>
-                    <button class="delete-btn" onclick="deleteMessage(${m.id})">删除</button>
+                    ${m.server_id ? `<span class="server-badge">${escapeHtml(serverName(m.server_id))}</span>` : ''}
+                    <button class="delete-btn" onclick="deleteMessage(${m.id}, this.closest('.message-card').dataset.server)">删除</button>
                 </div>
             </div>
         `).join('')}
@@ -116,37 +147,37 @@ async function loadMoreMessages() {
     renderMessages();
 
     try {
-        // 获取当前最旧的消息 ID，使用 since 参数加载更早的消息
-        const oldestMessageId = AppState.messages[AppState.messages.length - 1]?.id;
+        // 使用上一页 paging 中的 since 加载更早的消息
+        const since = AppState.nextSince;
         
-        if (!oldestMessageId) {
+        if (!since) {
             AppState.loadingMore = false;
             renderMessages();
             return;
         }
 
-        console.log('Fetching with since:', oldestMessageId, 'limit:', 10);
+        console.log('Fetching with since:', since, 'limit:', 10);
         const result = await invoke('fetch_messages', { 
-            since: oldestMessageId,
+            serverId: AppState.serverId,
+            since,
             limit: 10,
             offset: null
         });
         console.log('Fetch result:', result);
         if (result.success) {
-            const newMessages = result.data || [];
+            const page = result.data;
+            const newMessages = page.messages || [];
             console.log('New messages received:', newMessages.length);
-            if (newMessages.length > 0) {
-                // 去重：过滤掉已存在的消息
-                const existingIds = new Set(AppState.messages.map(m => m.id));
-                const uniqueNewMessages = newMessages.filter(m => !existingIds.has(m.id));
-                
-                console.log('Filtered unique messages:', uniqueNewMessages.length);
-                AppState.messages = [...AppState.messages, ...uniqueNewMessages];
-                AppState.hasMoreMessages = uniqueNewMessages.length >= 10;
-                console.log('Total messages:', AppState.messages.length, 'hasMore:', AppState.hasMoreMessages);
-            } else {
-                AppState.hasMoreMessages = false;
-            }
+            // 去重：过滤掉已存在的消息
+            const existingIds = new Set(AppState.messages.map(m => m.id));
+            const uniqueNewMessages = newMessages.filter(m => !existingIds.has(m.id));
+
+            console.log('Filtered unique messages:', uniqueNewMessages.length);
+            AppState.messages = [...AppState.messages, ...uniqueNewMessages];
+            // 最后一页的 paging 没有 next
+            AppState.hasMoreMessages = !!page.paging.next;
+            AppState.nextSince = page.paging.since;
+            console.log('Total messages:', AppState.messages.length, 'hasMore:', AppState.hasMoreMessages);
         } else {
             console.error('Fetch failed:', result.error);
         }
@@ -188,22 +219,26 @@ async function refreshMessages() {
     try {
         console.log('Fetching messages...');
         const result = await invoke('fetch_messages', { 
+            serverId: AppState.serverId,
             since: null,
             limit: 10,
             offset: 0
         });
         console.log('Fetch result:', result);
         if (result.success) {
-            const allMessages = result.data || [];
-            AppState.messages = allMessages;
-            AppState.hasMoreMessages = allMessages.length >= 10;
+            const page = result.data;
+            AppState.messages = page.messages || [];
+            AppState.hasMoreMessages = !!page.paging.next;
+            AppState.nextSince = page.paging.since;
             console.log('Messages loaded:', AppState.messages.length, 'hasMore:', AppState.hasMoreMessages);
             updateUIState({ loading: false });
         } else {
             console.error('Fetch failed:', result.error);
+            // 服务器不可用时展示本地缓存的历史消息
+            const cached = await loadCachedMessages();
             updateUIState({
                 loading: false,
-                error: result.error || '获取消息失败'
+                error: (result.error || '获取消息失败') + (cached ? '（显示本地缓存）' : '')
             });
         }
     } catch (e) {
@@ -212,14 +247,34 @@ async function refreshMessages() {
     }
 }
 
+// 读取本地缓存的消息，返回是否有缓存
+async function loadCachedMessages() {
+    try {
+        const result = await invoke('query_local_messages', {
+            query: { server_id: AppState.serverId, limit: 100 }
+        });
+        if (result.success && result.data && result.data.length > 0) {
+            AppState.messages = result.data;
+            AppState.hasMoreMessages = false;
+            return true;
+        }
+    } catch (e) {
+        console.error('读取本地缓存失败:', e);
+    }
+    return false;
+}
+
 // 删除消息
-async function deleteMessage(messageId) {
+async function deleteMessage(messageId, serverId) {
     if (!confirm('确定要删除这条消息吗？')) return;
 
+    serverId = serverId || AppState.serverId;
     try {
-        const result = await invoke('delete_message', { messageId });
+        const result = await invoke('delete_message', { serverId, messageId });
         if (result.success) {
-            AppState.messages = AppState.messages.filter(m => m.id !== messageId);
+            AppState.messages = AppState.messages.filter(
+                m => !(m.id === messageId && (m.server_id || AppState.serverId) === serverId)
+            );
             updateUIState({ error: null });
         } else {
             updateUIState({ error: result.error || '删除失败' });
@@ -528,12 +583,85 @@ async function setDefaultConfig(id) {
     }
 }
 
+// TLS 设置输入框与 ServerConfig.tls 字段的对应关系
+const TLS_FIELDS = {
+    modalCaFile: 'ca_file',
+    modalPinSha256: 'pin_sha256',
+    modalClientCert: 'client_cert_file',
+    modalClientKey: 'client_key_file'
+};
+
+function fillTlsFields(tls = {}) {
+    for (const [inputId, key] of Object.entries(TLS_FIELDS)) {
+        document.getElementById(inputId).value = tls[key] || '';
+    }
+}
+
+function readTlsFields() {
+    const tls = {};
+    for (const [inputId, key] of Object.entries(TLS_FIELDS)) {
+        tls[key] = document.getElementById(inputId).value.trim() || null;
+    }
+    return tls;
+}
+
+// 代理设置输入框（全局设置和服务器配置共用，以前缀区分）
+function fillProxyFields(prefix, proxy) {
+    document.getElementById(`${prefix}Mode`).value = proxy ? proxy.mode : '';
+    document.getElementById(`${prefix}Url`).value = proxy?.url || '';
+    document.getElementById(`${prefix}Username`).value = proxy?.username || '';
+    document.getElementById(`${prefix}Password`).value = proxy?.password || '';
+    document.getElementById(`${prefix}NoProxy`).value = proxy?.no_proxy || '';
+}
+
+// 未选择代理模式时返回 null，表示使用全局设置
+function readProxyFields(prefix) {
+    const mode = document.getElementById(`${prefix}Mode`).value;
+    if (!mode) return null;
+    const value = id => document.getElementById(`${prefix}${id}`).value.trim() || null;
+    return {
+        mode,
+        url: value('Url'),
+        username: value('Username'),
+        password: document.getElementById(`${prefix}Password`).value || null,
+        no_proxy: value('NoProxy')
+    };
+}
+
+// 自定义请求头以 "名称: 值" 每行一个的形式编辑
+function fillHeaderFields(headers = {}, basicAuth = null) {
+    document.getElementById('modalHeaders').value = Object.entries(headers)
+        .map(([name, value]) => `${name}: ${value}`)
+        .join('\n');
+    document.getElementById('modalBasicAuthUsername').value = basicAuth?.username || '';
+    document.getElementById('modalBasicAuthPassword').value = basicAuth?.password || '';
+}
+
+function readHeaderFields() {
+    const headers = {};
+    for (const line of document.getElementById('modalHeaders').value.split('\n')) {
+        const index = line.indexOf(':');
+        if (index <= 0) continue;
+        headers[line.slice(0, index).trim()] = line.slice(index + 1).trim();
+    }
+    const username = document.getElementById('modalBasicAuthUsername').value.trim();
+    const basic_auth = username
+        ? { username, password: document.getElementById('modalBasicAuthPassword').value }
+        : null;
+    return { headers, basic_auth };
+}
+
 // 显示新增配置模态框
 function showAddConfigModal() {
     document.getElementById('modalTitle').textContent = '新增配置';
     document.getElementById('modalServerUrl').value = '';
     document.getElementById('modalToken').value = '';
+    document.getElementById('modalUsername').value = '';
+    document.getElementById('modalPassword').value = '';
     document.getElementById('modalConfigName').value = '';
+    fillTlsFields();
+    fillProxyFields('modalProxy', null);
+    fillHeaderFields();
     document.getElementById('configModal').classList.add('show');
 }
 
@@ -545,13 +673,20 @@ function editConfig(id) {
     document.getElementById('modalTitle').textContent = '编辑配置';
     document.getElementById('modalServerUrl').value = config.server_url;
     document.getElementById('modalToken').value = config.token;
+    document.getElementById('modalUsername').value = '';
+    document.getElementById('modalPassword').value = '';
     document.getElementById('modalConfigName').value = config.name;
+    fillTlsFields(config.tls);
+    fillProxyFields('modalProxy', config.proxy);
+    fillHeaderFields(config.headers, config.basic_auth);
     document.getElementById('configModal').classList.add('show');
     document.getElementById('configModal').dataset.editId = id;
 }
 
 // 关闭配置模态框
 function closeConfigModal() {
+    // 密码只在登录时使用，关闭时立即清空
+    document.getElementById('modalPassword').value = '';
     document.getElementById('configModal').classList.remove('show');
     delete document.getElementById('configModal').dataset.editId;
 }
@@ -560,16 +695,23 @@ function closeConfigModal() {
 async function saveConfigFromModal() {
     const serverUrl = document.getElementById('modalServerUrl').value.trim();
     const token = document.getElementById('modalToken').value.trim();
+    const username = document.getElementById('modalUsername').value.trim();
+    const password = document.getElementById('modalPassword').value;
     const configName = document.getElementById('modalConfigName').value.trim();
+    const tls = readTlsFields();
+    const proxy = readProxyFields('modalProxy');
+    const { headers, basic_auth } = readHeaderFields();
     const modal = document.getElementById('configModal');
     const editId = modal.dataset.editId;
+    // 填写了账号密码时登录并创建新令牌
+    const useLogin = username && password;
 
     if (!serverUrl) {
         alert('请输入服务器 URL');
         return;
     }
-    if (!token) {
-        alert('请输入 Token');
+    if (!token && !useLogin) {
+        alert('请输入 Token，或填写用户名和密码登录');
         return;
     }
     if (!configName) {
@@ -586,14 +728,40 @@ async function saveConfigFromModal() {
     }
 
     try {
-        if (editId) {
+        if (useLogin) {
+            const result = await invoke('login_to_gotify', {
+                req: {
+                    server_url: serverUrl,
+                    username,
+                    password,
+                    name: configName,
+                    server_id: editId || null,
+                    tls,
+                    proxy,
+                    headers,
+                    basic_auth
+                }
+            });
+            if (result.success) {
+                await loadConfigs();
+                closeConfigModal();
+            } else {
+                alert(result.error || '登录失败');
+            }
+        } else if (editId) {
             // 编辑现有配置
             console.log('Updating config:', { id: editId, name: configName, serverUrl: serverUrl });
             const result = await invoke('update_config', {
-                id: editId,
-                name: configName,
-                serverUrl: serverUrl,
-                token
+                req: {
+                    id: editId,
+                    name: configName,
+                    server_url: serverUrl,
+                    token,
+                    tls,
+                    proxy,
+                    headers,
+                    basic_auth
+                }
             });
             if (result.success) {
                 await loadConfigs();
@@ -607,7 +775,11 @@ async function saveConfigFromModal() {
             const result = await invoke('save_config', {
                 name: configName,
                 serverUrl: serverUrl,
-                token
+                token,
+                tls,
+                proxy,
+                headers,
+                basicAuth: basic_auth
             });
             if (result.success) {
                 await loadConfigs();
@@ -646,12 +818,14 @@ async function connectToServer(serverUrl, token, configName = null, configId = n
 
     try {
         const result = await invoke('connect_to_gotify', { 
-            req: { server_url: serverUrl, token } 
+            req: { server_url: serverUrl, token, server_id: configId } 
         });
 
         if (result.success) {
             AppState.connected = true;
             AppState.serverUrl = serverUrl;
+            AppState.serverId = result.data;
+            AppState.connectionStatus = AppState.connectionStatuses[result.data] || { state: 'connecting' };
             if (configName) {
                 AppState.configName = configName;
             }
@@ -672,28 +846,67 @@ async function connectToServer(serverUrl, token, configName = null, configId = n
     }
 }
 
-// 自动连接默认配置
+// 连接所有启用的服务器，并显示默认配置的消息
 async function autoConnectDefault() {
     try {
+        const connected = await invoke('connect_all_servers');
+        const connectedIds = connected.success ? connected.data || [] : [];
         const result = await invoke('get_default_config');
         if (result.success && result.data) {
             const config = result.data;
             console.log('Auto-connecting to default config:', config.name, 'id:', config.id);
-            // 直接连接，不依赖表单元素
-            await connectToServer(config.server_url, config.token, config.name, config.id);
+            if (connectedIds.includes(config.id)) {
+                // 已随其他服务器一起连接，直接切换到该服务器
+                AppState.connected = true;
+                AppState.serverUrl = config.server_url;
+                AppState.serverId = config.id;
+                AppState.configName = config.name;
+                AppState.currentConfigId = config.id;
+                AppState.connectionStatus = AppState.connectionStatuses[config.id] || { state: 'connecting' };
+                updateUIState({ error: null });
+                await refreshMessages();
+            } else {
+                // 直接连接，不依赖表单元素
+                await connectToServer(config.server_url, config.token, config.name, config.id);
+            }
         }
     } catch (e) {
         console.error('Auto-connect failed:', e);
     }
 }
 
+// 监听连接状态变化
+listen('connection-status', (event) => {
+    console.log('连接状态:', event.payload);
+    const status = event.payload;
+    AppState.connectionStatuses[status.server_id] = status;
+    if (status.server_id === AppState.serverId) {
+        updateUIState({ connectionStatus: status });
+    }
+});
+
+// 监听未读数变化，显示在窗口标题中
+listen('unread-count', (event) => {
+    const total = event.payload.total || 0;
+    document.title = total > 0 ? `(${total}) Gotify Desktop` : 'Gotify Desktop';
+});
+
+// 网络恢复后立即重连，不必等待退避计时
+window.addEventListener('online', () => {
+    if (AppState.connected) {
+        invoke('reconnect_now').catch(e => console.error('重连失败:', e));
+    }
+});
+
 // 监听新消息
 listen('new-message', (event) => {
     console.log('收到新消息:', event.payload);
     const newMessage = event.payload;
     
-    // 检查是否已存在相同 ID 的消息
-    const existingIndex = AppState.messages.findIndex(m => m.id === newMessage.id);
+    // 统一收件箱：所有已连接服务器的消息都显示，不同服务器的消息 ID 可能相同
+    const existingIndex = AppState.messages.findIndex(
+        m => m.id === newMessage.id && (m.server_id || AppState.serverId) === newMessage.server_id
+    );
     if (existingIndex === -1) {
         // 新消息添加到列表开头
         AppState.messages.unshift(newMessage);
@@ -705,14 +918,7 @@ listen('new-message', (event) => {
     } else {
         console.log('消息已存在，跳过添加:', newMessage.id);
     }
-
-    // 发送系统通知
-    if (AppState.settings.enable_notifications) {
-        invoke('send_notification', {
-            title: newMessage.title || '新消息',
-            body: newMessage.message
-        }).catch(e => console.error('发送通知失败:', e));
-    }
+    // 系统通知由后端消息管道统一发送
 });
 
 // 加载应用设置
@@ -726,21 +932,98 @@ async function loadAppSettings() {
             document.getElementById('silentStart').checked = AppState.settings.silent_start;
             document.getElementById('enableNotifications').checked = AppState.settings.enable_notifications;
             document.getElementById('logLevel').value = AppState.settings.log_level || 'info';
+            fillProxyFields('proxy', AppState.settings.proxy || { mode: 'system' });
         }
+        await loadVaultStatus();
     } catch (e) {
         console.error('加载应用设置错误:', e);
     }
 }
 
+// 令牌保险库状态
+async function loadVaultStatus() {
+    try {
+        const result = await invoke('get_vault_status');
+        if (!result.success) return;
+        const status = result.data;
+        document.getElementById('vaultMode').value = status.mode;
+        document.getElementById('vaultKeyFile').value = status.key_file || '';
+        let text = status.mode === 'plaintext'
+            ? '令牌以明文保存在配置文件中（仅当前用户可读）'
+            : '令牌已加密保存，配置文件中只保留引用';
+        if (status.locked) {
+            text = '保险库已锁定，输入密码后点击“解锁”';
+            updateUIState({ error: '令牌保险库已锁定，请在设置中输入密码解锁' });
+        }
+        if (!status.secret_service_available) {
+            text += '；系统密钥环不可用（需要 secret-tool）';
+        }
+        document.getElementById('vaultStatus').textContent = text;
+    } catch (e) {
+        console.error('加载保险库状态错误:', e);
+    }
+}
+
+// 更改令牌存储方式，所有令牌会用新的密钥重新加密
+async function configureVault() {
+    const passphraseInput = document.getElementById('vaultPassphrase');
+    const req = {
+        mode: document.getElementById('vaultMode').value,
+        key_file: document.getElementById('vaultKeyFile').value.trim() || null,
+        passphrase: passphraseInput.value || null
+    };
+    if (req.mode === 'passphrase' && !req.passphrase) {
+        alert('请输入保险库密码');
+        return;
+    }
+    if (req.mode === 'key_file' && !req.key_file) {
+        alert('请输入密钥文件路径');
+        return;
+    }
+    try {
+        const result = await invoke('configure_vault', { req });
+        passphraseInput.value = '';
+        if (!result.success) {
+            alert(result.error || '更改令牌存储方式失败');
+            return;
+        }
+        await loadVaultStatus();
+        alert('令牌存储方式已更改');
+    } catch (e) {
+        alert(`更改令牌存储方式错误: ${e}`);
+    }
+}
+
+// 输入密码解锁保险库，随后重新连接服务器
+async function unlockVault() {
+    const passphraseInput = document.getElementById('vaultPassphrase');
+    try {
+        const result = await invoke('unlock_vault', { passphrase: passphraseInput.value });
+        passphraseInput.value = '';
+        if (!result.success) {
+            alert(result.error || '解锁失败');
+            return;
+        }
+        updateUIState({ error: null });
+        await loadVaultStatus();
+        await loadConfigs();
+        await autoConnectDefault();
+    } catch (e) {
+        alert(`解锁错误: ${e}`);
+    }
+}
+
 // 保存应用设置
 async function saveAppSettings() {
     try {
         const settings = {
+            ...AppState.settings,
             enable_autostart: document.getElementById('enableAutostart').checked,
             minimize_to_tray: document.getElementById('minimizeToTray').checked,
             silent_start: document.getElementById('silentStart').checked,
             enable_notifications: document.getElementById('enableNotifications').checked,
-            log_level: document.getElementById('logLevel').value
+            log_level: document.getElementById('logLevel').value,
+            proxy: readProxyFields('proxy')
         };
 
         // 保存设置到文件
@@ -814,6 +1097,7 @@ document.addEventListener('DOMContentLoaded', () => {
                 if (result.success) {
                     AppState.connected = true;
                     AppState.serverUrl = url;
+                    AppState.serverId = result.data;
                     updateUIState({ loading: false });
                     await refreshMessages();
                 } else {
@@ -833,8 +1117,9 @@ document.addEventListener('DOMContentLoaded', () => {
     if (disconnectBtn) {
         disconnectBtn.addEventListener('click', async () => {
             try {
-                await invoke('disconnect_gotify');
+                await invoke('disconnect_gotify', { serverId: AppState.serverId });
                 AppState.connected = false;
+                AppState.serverId = null;
                 AppState.messages = [];
                 AppState.configName = null;
                 updateUIState({ error: null });